@@ -0,0 +1,138 @@
+//! Derive macros of `mepoo`, re-exported by it with the `derive` feature.
+
+use proc_macro::TokenStream;
+use proc_macro2::Span;
+use quote::{format_ident, quote};
+use syn::{
+    parse_macro_input, Data, DeriveInput, Error, Fields, GenericParam, Lifetime, LifetimeParam,
+};
+
+/// Derives `PooledSoA` for a struct of named fields, by which its values are stored field by field in `SoAPool`.
+///
+/// For a struct `Foo`, this also defines `FooRef` and `FooMut` of the same visibility,
+/// whose fields are the references to the fields of a value, and `FooColumns`, which holds the columns.
+#[proc_macro_derive(PooledSoA)]
+pub fn derive_pooled_soa(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+fn expand(input: DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => {
+                return Err(Error::new_spanned(
+                    &input.ident,
+                    "PooledSoA requires a struct of named fields",
+                ))
+            }
+        },
+        _ => {
+            return Err(Error::new_spanned(
+                &input.ident,
+                "PooledSoA can be derived only for a struct",
+            ))
+        }
+    };
+    let (vis, name) = (&input.vis, &input.ident);
+    let columns = format_ident!("{}Columns", name);
+    let ref_name = format_ident!("{}Ref", name);
+    let mut_name = format_ident!("{}Mut", name);
+    let names = fields
+        .iter()
+        .map(|f| f.ident.as_ref().unwrap())
+        .collect::<Vec<_>>();
+    let types = fields.iter().map(|f| &f.ty).collect::<Vec<_>>();
+    let vises = fields.iter().map(|f| &f.vis).collect::<Vec<_>>();
+
+    // the references borrow the columns for a lifetime added in front of the generics of the struct
+    let lt = Lifetime::new("'__a", Span::call_site());
+    let generics = &input.generics;
+    let mut ref_generics = generics.clone();
+    ref_generics
+        .params
+        .insert(0, GenericParam::Lifetime(LifetimeParam::new(lt.clone())));
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    let (_, ref_ty_generics, _) = ref_generics.split_for_impl();
+
+    let ref_doc = format!("The references to the fields of a `{}` in `SoAPool`.", name);
+    let mut_doc = format!(
+        "The mutable references to the fields of a `{}` in `SoAPool`.",
+        name
+    );
+    let columns_doc = format!("The columns of the fields of `{}` in `SoAPool`.", name);
+    let private = quote!(::mepoo::__private);
+
+    Ok(quote! {
+        #[doc = #ref_doc]
+        #[allow(dead_code)]
+        #vis struct #ref_name #ref_generics #where_clause {
+            #(#vises #names: &#lt #types,)*
+        }
+
+        #[doc = #mut_doc]
+        #[allow(dead_code)]
+        #vis struct #mut_name #ref_generics #where_clause {
+            #(#vises #names: &#lt mut #types,)*
+        }
+
+        #[doc = #columns_doc]
+        #vis struct #columns #generics #where_clause {
+            #(#names: #private::Vec<#private::MaybeUninit<#types>>,)*
+        }
+
+        #[automatically_derived]
+        impl #impl_generics ::core::default::Default for #columns #ty_generics #where_clause {
+            fn default() -> Self {
+                Self {
+                    #(#names: #private::Vec::new(),)*
+                }
+            }
+        }
+
+        #[automatically_derived]
+        unsafe impl #impl_generics ::mepoo::PooledSoA for #name #ty_generics #where_clause {
+            type Columns = #columns #ty_generics;
+            type Ref<#lt> = #ref_name #ref_ty_generics where Self: #lt;
+            type Mut<#lt> = #mut_name #ref_ty_generics where Self: #lt;
+
+            fn push_slot(columns: &mut Self::Columns) {
+                #(columns.#names.push(#private::MaybeUninit::uninit());)*
+            }
+
+            unsafe fn write(columns: &mut Self::Columns, index: usize, value: Self) {
+                #(columns.#names[index].write(value.#names);)*
+            }
+
+            unsafe fn read(columns: &Self::Columns, index: usize) -> Self {
+                unsafe {
+                    Self {
+                        #(#names: columns.#names[index].assume_init_read(),)*
+                    }
+                }
+            }
+
+            unsafe fn get(columns: &Self::Columns, index: usize) -> Self::Ref<'_> {
+                unsafe {
+                    #ref_name {
+                        #(#names: columns.#names[index].assume_init_ref(),)*
+                    }
+                }
+            }
+
+            unsafe fn get_mut<#lt>(columns: *mut Self::Columns, index: usize) -> Self::Mut<#lt>
+            where
+                Self: #lt,
+            {
+                unsafe {
+                    #mut_name {
+                        #(#names: (*(*columns).#names.as_mut_ptr().add(index)).assume_init_mut(),)*
+                    }
+                }
+            }
+        }
+    })
+}
@@ -0,0 +1,181 @@
+use crate::{Pool, Ptr, Ref};
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use core::any::{Any, TypeId};
+
+/// A pointer to a value of any type in `AnyPool`, which can be downcast to `Ptr<T>` of the type.
+pub struct AnyPtr {
+    type_id: TypeId,
+    ptr: Ptr<()>,
+}
+
+impl AnyPtr {
+    pub fn is<T: 'static>(&self) -> bool {
+        self.type_id == TypeId::of::<T>()
+    }
+
+    pub fn downcast<T: 'static>(self) -> Option<Ptr<T>> {
+        self.is::<T>().then(|| self.ptr.cast())
+    }
+}
+
+impl<T: 'static> From<Ptr<T>> for AnyPtr {
+    fn from(ptr: Ptr<T>) -> Self {
+        Self {
+            type_id: TypeId::of::<T>(),
+            ptr: ptr.cast(),
+        }
+    }
+}
+
+trait ErasedPool {
+    fn free(&mut self, p: Ptr<()>) -> bool;
+    fn len(&self) -> usize;
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+impl<T: 'static> ErasedPool for Pool<T> {
+    fn free(&mut self, p: Ptr<()>) -> bool {
+        Pool::free(self, p.cast())
+    }
+    fn len(&self) -> usize {
+        Pool::len(self)
+    }
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+/// A pool of values of different types, which holds a `Pool<T>` for each type `T`.
+/// The type of a value is checked by `TypeId` when accessed by `AnyPtr`.
+#[derive(Default)]
+pub struct AnyPool {
+    pools: BTreeMap<TypeId, Box<dyn ErasedPool>>,
+}
+
+impl AnyPool {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of values of all types.
+    pub fn len(&self) -> usize {
+        self.pools.values().map(|pool| pool.len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the pool of `T`, or `None` if no value of `T` has been allocated.
+    pub fn pool<T: 'static>(&self) -> Option<&Pool<T>> {
+        let pool = self.pools.get(&TypeId::of::<T>())?;
+        pool.as_any().downcast_ref()
+    }
+
+    /// Returns the pool of `T`, creating it if no value of `T` has been allocated.
+    pub fn pool_mut<T: 'static>(&mut self) -> &mut Pool<T> {
+        let pool = self
+            .pools
+            .entry(TypeId::of::<T>())
+            .or_insert_with(|| Box::new(Pool::<T>::new()));
+        pool.as_any_mut().downcast_mut().unwrap()
+    }
+
+    pub fn alloc<T: 'static>(&mut self, value: T) -> AnyPtr {
+        self.pool_mut().alloc(value).into()
+    }
+
+    /// Returns `None` if `p` is freed or not of `T`.
+    pub fn get<T: 'static>(&self, p: AnyPtr) -> Option<Ref<'_, T>> {
+        self.pool()?.get(p.downcast()?)
+    }
+
+    pub fn get_mut<T: 'static>(&mut self, p: AnyPtr) -> Option<&mut T> {
+        let p = p.downcast()?;
+        self.pool_mut().get_mut(p)
+    }
+
+    pub fn free(&mut self, p: AnyPtr) -> bool {
+        match self.pools.get_mut(&p.type_id) {
+            Some(pool) => pool.free(p.ptr),
+            None => false,
+        }
+    }
+
+    /// Same as `free`, but moves the value out, or returns `None` if `p` is freed or not of `T`.
+    pub fn take<T: 'static>(&mut self, p: AnyPtr) -> Option<T> {
+        let p = p.downcast()?;
+        self.pool_mut().take(p)
+    }
+}
+
+impl core::fmt::Debug for AnyPool {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(f, "AnyPool {{ len: {} }}", self.len())
+    }
+}
+
+impl core::fmt::Debug for AnyPtr {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(f, "AnyPtr({:?}, {:?})", self.type_id, self.ptr)
+    }
+}
+impl Clone for AnyPtr {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+impl Copy for AnyPtr {}
+impl PartialEq for AnyPtr {
+    fn eq(&self, rhs: &Self) -> bool {
+        self.type_id == rhs.type_id && self.ptr == rhs.ptr
+    }
+}
+impl Eq for AnyPtr {}
+impl core::hash::Hash for AnyPtr {
+    fn hash<H: core::hash::Hasher>(&self, state: &mut H) {
+        self.type_id.hash(state);
+        self.ptr.hash(state);
+    }
+}
+
+#[test]
+fn test() {
+    use alloc::string::String;
+
+    enum Expr {
+        Num(f64),
+        Add(AnyPtr, AnyPtr),
+    }
+    let mut pool = AnyPool::new();
+    let x = pool.alloc(Expr::Num(1.0));
+    let name = pool.alloc(String::from("y"));
+    let sum = pool.alloc(Expr::Add(x, name));
+    assert_eq!(pool.len(), 3);
+    assert!(sum.is::<Expr>() && !sum.is::<String>());
+    assert!(pool.get::<String>(x).is_none());
+    assert_eq!(pool.get::<String>(name).unwrap().as_str(), "y");
+    pool.get_mut::<String>(name).unwrap().push('!');
+    match *pool.get::<Expr>(sum).unwrap() {
+        Expr::Add(a, b) => {
+            assert!(matches!(*pool.get::<Expr>(a).unwrap(), Expr::Num(v) if v == 1.0));
+            assert_eq!(pool.get::<String>(b).unwrap().as_str(), "y!");
+        }
+        Expr::Num(_) => panic!(),
+    }
+    assert_eq!(pool.pool::<Expr>().unwrap().len(), 2);
+    assert!(pool.pool::<u8>().is_none());
+    assert!(pool.free(x));
+    assert!(!pool.free(x));
+    assert_eq!(pool.take::<String>(name).as_deref(), Some("y!"));
+    assert_eq!(pool.len(), 1);
+    assert_eq!(
+        pool.pool::<Expr>().unwrap().iter().next().map(|(p, _)| p),
+        sum.downcast()
+    );
+}
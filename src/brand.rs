@@ -0,0 +1,114 @@
+use crate::{BlockStorage, Heap, Pool, Ptr};
+use core::marker::PhantomData;
+
+/// An invariant lifetime which identifies a `BrandedPool` at compile time.
+type Brand<'brand> = PhantomData<fn(&'brand ()) -> &'brand ()>;
+
+/// A pool borrowed by `Pool::scope`, whose pointers are statically bound to it by the lifetime `'brand`.
+/// Since a `BrandedPtr<'brand, T>` can be used only with this pool, no `pool_id` check is needed.
+pub struct BrandedPool<'brand, T, S: BlockStorage = Heap> {
+    pool: &'brand mut Pool<T, S>,
+    _brand: Brand<'brand>,
+}
+
+/// A pointer which can be used only with the `BrandedPool` of the same `'brand`.
+pub struct BrandedPtr<'brand, T> {
+    ptr: Ptr<T>,
+    _brand: Brand<'brand>,
+}
+
+impl<T, S: BlockStorage> Pool<T, S> {
+    /// Calls `f` with this pool branded by a lifetime unique to the call.
+    pub fn scope<R>(&mut self, f: impl for<'brand> FnOnce(BrandedPool<'brand, T, S>) -> R) -> R {
+        f(BrandedPool {
+            pool: self,
+            _brand: PhantomData,
+        })
+    }
+}
+
+impl<'brand, T, S: BlockStorage> BrandedPool<'brand, T, S> {
+    pub fn pool(&self) -> &Pool<T, S> {
+        self.pool
+    }
+
+    pub fn alloc(&mut self, value: T) -> BrandedPtr<'brand, T> {
+        BrandedPtr {
+            ptr: self.pool.alloc(value),
+            _brand: PhantomData,
+        }
+    }
+
+    /// Brands `p` after checking that it belongs to this pool.
+    pub fn brand(&self, p: Ptr<T>) -> Option<BrandedPtr<'brand, T>> {
+        (p.pool_id == self.pool.id()).then_some(BrandedPtr {
+            ptr: p,
+            _brand: PhantomData,
+        })
+    }
+
+    pub fn get(&self, p: BrandedPtr<'brand, T>) -> Option<&T> {
+        unsafe { p.ptr.as_ref() }.map(|r| r.get())
+    }
+
+    pub fn get_mut(&mut self, p: BrandedPtr<'brand, T>) -> Option<&mut T> {
+        unsafe { p.ptr.as_mut() }
+    }
+
+    pub fn free(&mut self, p: BrandedPtr<'brand, T>) -> bool {
+        self.pool.take_entry(p.ptr).is_some()
+    }
+
+    pub fn take(&mut self, p: BrandedPtr<'brand, T>) -> Option<T> {
+        self.pool.take_entry(p.ptr)
+    }
+}
+
+impl<'brand, T> BrandedPtr<'brand, T> {
+    pub fn unbrand(self) -> Ptr<T> {
+        self.ptr
+    }
+}
+
+impl<'brand, T> core::fmt::Debug for BrandedPtr<'brand, T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(f, "BrandedPtr({:?})", self.ptr)
+    }
+}
+impl<'brand, T> Clone for BrandedPtr<'brand, T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+impl<'brand, T> Copy for BrandedPtr<'brand, T> {}
+impl<'brand, T> PartialEq for BrandedPtr<'brand, T> {
+    fn eq(&self, rhs: &Self) -> bool {
+        self.ptr == rhs.ptr
+    }
+}
+impl<'brand, T> Eq for BrandedPtr<'brand, T> {}
+impl<'brand, T> core::hash::Hash for BrandedPtr<'brand, T> {
+    fn hash<H: core::hash::Hasher>(&self, state: &mut H) {
+        self.ptr.hash(state)
+    }
+}
+
+#[test]
+fn test() {
+    let mut pool = Pool::new();
+    let mut other = Pool::new();
+    let q = other.alloc(0);
+    let p = pool.scope(|mut pool| {
+        let p = pool.alloc(1);
+        *pool.get_mut(p).unwrap() += 1;
+        assert_eq!(pool.get(p), Some(&2));
+        assert!(pool.brand(q).is_none());
+        let r = pool.brand(p.unbrand()).unwrap();
+        assert_eq!(r, p);
+        assert_eq!(pool.take(p), Some(2));
+        assert!(!pool.free(r));
+        assert!(pool.get(r).is_none());
+        pool.alloc(3).unbrand()
+    });
+    assert_eq!(pool[p], 3);
+}
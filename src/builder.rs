@@ -0,0 +1,173 @@
+use crate::Pool;
+
+/// How the size of a new block is determined when a `Pool` grows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GrowthPolicy {
+    /// Every block has the same size.
+    Fixed,
+    /// Every block is twice as large as the previous one.
+    Doubling,
+}
+
+/// Which vacant entry a `Pool` allocates next.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FreeListPolicy {
+    /// The entry freed last is allocated first, which takes constant time.
+    Lifo,
+    /// The vacant entries are kept in block order, so that the values are packed into the lowest blocks
+    /// and the higher blocks can become empty. Freeing takes time proportional to the number of vacant entries before it.
+    LowestFirst,
+}
+
+/// A builder of `Pool` to configure its block size and capacity.
+#[derive(Debug, Clone)]
+pub struct PoolBuilder {
+    block_size: usize,
+    initial_capacity: usize,
+    max_capacity: Option<usize>,
+    growth: GrowthPolicy,
+    free_list: FreeListPolicy,
+}
+
+impl PoolBuilder {
+    pub fn new() -> Self {
+        Self {
+            block_size: Pool::<()>::DEFAULT_BLOCK_SIZE,
+            initial_capacity: 0,
+            max_capacity: None,
+            growth: GrowthPolicy::Fixed,
+            free_list: FreeListPolicy::Lifo,
+        }
+    }
+
+    /// Sets the size of the blocks, or of the first block for `GrowthPolicy::Doubling`.
+    pub fn block_size(mut self, block_size: usize) -> Self {
+        self.block_size = block_size;
+        self
+    }
+
+    /// Sets the number of entries allocated in advance.
+    pub fn initial_capacity(mut self, initial_capacity: usize) -> Self {
+        self.initial_capacity = initial_capacity;
+        self
+    }
+
+    /// Sets the number of entries beyond which the pool never grows.
+    /// The last block is shrunk not to exceed it.
+    pub fn max_capacity(mut self, max_capacity: usize) -> Self {
+        self.max_capacity = Some(max_capacity);
+        self
+    }
+
+    pub fn growth(mut self, growth: GrowthPolicy) -> Self {
+        self.growth = growth;
+        self
+    }
+
+    pub fn free_list(mut self, free_list: FreeListPolicy) -> Self {
+        self.free_list = free_list;
+        self
+    }
+
+    /// # Panics
+    /// Panics if the block size is zero, or if the initial capacity exceeds the maximum capacity.
+    pub fn build<T>(&self) -> Pool<T> {
+        let mut pool = Pool::with_block_size(self.block_size);
+        pool.growth = self.growth;
+        pool.max_capacity = self.max_capacity;
+        pool.free_list = self.free_list;
+        pool.grow_to_capacity(self.initial_capacity);
+        pool
+    }
+}
+
+impl Default for PoolBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[test]
+fn test() {
+    let mut pool = PoolBuilder::new()
+        .block_size(4)
+        .initial_capacity(5)
+        .max_capacity(10)
+        .build();
+    assert_eq!(pool.capacity(), 8);
+    for i in 0..10 {
+        assert!(pool.try_alloc(i).is_ok());
+    }
+    assert_eq!(pool.capacity(), 10);
+    assert_eq!(pool.try_alloc(10), Err(10));
+    pool.assert_consistent();
+
+    let mut pool = PoolBuilder::new()
+        .block_size(2)
+        .growth(GrowthPolicy::Doubling)
+        .build();
+    for i in 0..15 {
+        pool.alloc(i);
+    }
+    let sizes = pool
+        .iter_blocks()
+        .map(|block| block.len())
+        .collect::<Vec<_>>();
+    assert_eq!(sizes, vec![2, 4, 8, 16]);
+    assert_eq!(pool.iter().count(), 15);
+}
+
+#[test]
+#[should_panic(expected = "maximum capacity")]
+fn alloc_beyond_max_capacity() {
+    let mut pool = PoolBuilder::new().max_capacity(1).build();
+    pool.alloc(0);
+    pool.alloc(1);
+}
+
+#[test]
+fn free_list_lowest_first() {
+    let mut pool = PoolBuilder::new()
+        .block_size(4)
+        .initial_capacity(8)
+        .free_list(FreeListPolicy::LowestFirst)
+        .build();
+    let ptrs = (0..12).map(|i| pool.alloc(i)).collect::<Vec<_>>();
+    let position = |pool: &Pool<i32>, p| {
+        let repr = pool.ptr_to_repr(p).unwrap();
+        4 * repr.block + repr.slot
+    };
+    assert!((0..12).all(|i| position(&pool, ptrs[i]) == i));
+    for &i in &[9, 2, 6, 0, 10] {
+        pool.free(ptrs[i]);
+    }
+    pool.assert_consistent();
+    let ptrs = (0..4).map(|i| pool.alloc(i)).collect::<Vec<_>>();
+    let positions = ptrs.iter().map(|&p| position(&pool, p)).collect::<Vec<_>>();
+    assert_eq!(positions, [0, 2, 6, 9]);
+    pool.retain(|_, &mut i| i > 5);
+    pool.reserve(8);
+    pool.assert_consistent();
+    assert_eq!(position(&pool, pool.free_list_head().unwrap()), 0);
+}
+
+#[test]
+fn free_list_lowest_first_after_swap() {
+    let mut pool = PoolBuilder::new()
+        .block_size(4)
+        .free_list(FreeListPolicy::LowestFirst)
+        .build();
+    let ptrs = (0..8).map(|i| pool.alloc(i)).collect::<Vec<_>>();
+    pool.free(ptrs[1]);
+    pool.free(ptrs[6]);
+    pool.swap_blocks(0, 1);
+    pool.assert_consistent();
+    let repr = |pool: &Pool<i32>, p| {
+        let repr = pool.ptr_to_repr(p).unwrap();
+        (repr.block, repr.slot)
+    };
+    let p = pool.alloc(8);
+    assert_eq!(repr(&pool, p), (0, 2));
+    let p = pool.alloc(9);
+    assert_eq!(repr(&pool, p), (1, 1));
+}
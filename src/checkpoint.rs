@@ -0,0 +1,152 @@
+use crate::{BlockStorage, Pool, PoolId};
+use alloc::vec::Vec;
+
+/// A point of time of a `Pool`, taken by `Pool::checkpoint`, to which `Pool::rollback` goes back.
+/// Checkpoints are nested; rolling back to a checkpoint discards the ones taken after it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Checkpoint {
+    pool_id: PoolId,
+    /// The length of the log at this checkpoint.
+    len: usize,
+}
+
+impl<T, S: BlockStorage> Pool<T, S> {
+    /// Takes a checkpoint, after which every allocation is logged until the outermost checkpoint
+    /// is rolled back or committed.
+    pub fn checkpoint(&mut self) -> Checkpoint {
+        let log = self.log.get_or_insert_with(Vec::new);
+        Checkpoint {
+            pool_id: self.id,
+            len: log.len(),
+        }
+    }
+
+    /// Returns `true` if a checkpoint is taken and not rolled back or committed yet.
+    pub fn in_checkpoint(&self) -> bool {
+        self.log.is_some()
+    }
+
+    fn check_checkpoint(&self, cp: Checkpoint) {
+        assert!(cp.pool_id == self.id);
+        match &self.log {
+            Some(log) if cp.len <= log.len() => {}
+            _ => panic!("checkpoint is already rolled back or committed"),
+        }
+    }
+
+    /// Frees every value allocated after `cp` and not freed yet, in the reverse order of the allocation,
+    /// and returns how many values are freed. This takes time proportional to the number of allocations after `cp`.
+    /// The values freed after `cp` are not restored.
+    ///
+    /// # Panics
+    /// Panics if `cp` does not belong to this pool, or it is discarded by rolling back or committing.
+    pub fn rollback(&mut self, cp: Checkpoint) -> usize {
+        self.check_checkpoint(cp);
+        let mut log = self.log.take().unwrap();
+        let count = log
+            .drain(cp.len..)
+            .rev()
+            .filter(|&p| self.drop_entry(p))
+            .count();
+        if cp.len > 0 {
+            self.log = Some(log);
+        }
+        count
+    }
+
+    /// Keeps the values allocated after `cp`. They are still freed by rolling back to an enclosing checkpoint.
+    ///
+    /// # Panics
+    /// Panics if `cp` does not belong to this pool, or it is discarded by rolling back or committing.
+    pub fn commit(&mut self, cp: Checkpoint) {
+        self.check_checkpoint(cp);
+        if cp.len == 0 {
+            self.log = None;
+        }
+    }
+}
+
+#[test]
+fn test() {
+    use alloc::rc::Rc;
+
+    let rc = Rc::new(());
+    let mut pool = Pool::with_block_size(4);
+    let a = pool.alloc(rc.clone());
+    assert!(!pool.in_checkpoint());
+    let outer = pool.checkpoint();
+    let b = pool.alloc(rc.clone());
+    let c = pool.alloc(rc.clone());
+    pool.free(c);
+    let inner = pool.checkpoint();
+    let ds = (0..10).map(|_| pool.alloc(rc.clone())).collect::<Vec<_>>();
+    pool.free(a);
+    assert_eq!(pool.rollback(inner), 10);
+    assert!(ds.iter().all(|&d| pool.get(d).is_none()));
+    assert!(pool.get(b).is_some() && pool.in_checkpoint());
+
+    let inner = pool.checkpoint();
+    let e = pool.alloc(rc.clone());
+    pool.commit(inner);
+    assert_eq!(pool.rollback(outer), 2);
+    assert!(pool.get(b).is_none() && pool.get(e).is_none());
+    assert!(!pool.in_checkpoint());
+    assert!(pool.is_empty());
+    assert_eq!(Rc::strong_count(&rc), 1);
+    pool.assert_consistent();
+
+    let cp = pool.checkpoint();
+    let f = pool.alloc(rc.clone());
+    pool.commit(cp);
+    assert!(!pool.in_checkpoint());
+    assert!(pool.get(f).is_some());
+}
+
+#[test]
+#[should_panic]
+fn discarded() {
+    let mut pool = Pool::new();
+    let outer = pool.checkpoint();
+    pool.alloc(0);
+    let inner = pool.checkpoint();
+    pool.rollback(outer);
+    pool.rollback(inner);
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn released() {
+    let mut pool = Pool::with_block_size(2);
+    let keep = pool.alloc(0);
+    let cp = pool.checkpoint();
+    let ptrs = (1..6).map(|i| pool.alloc(i)).collect::<Vec<_>>();
+    for &p in &ptrs[..3] {
+        pool.free(p);
+    }
+    let map = unsafe { pool.compact() };
+    assert_eq!(unsafe { pool.shrink_to_fit() }, 0);
+    assert_eq!(pool.rollback(cp), 2);
+    assert_eq!(pool.len(), 1);
+    assert_eq!(pool[*map.get(&keep).unwrap_or(&keep)], 0);
+    pool.assert_consistent();
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn moved_to_back() {
+    let mut pool = Pool::with_block_size(4);
+    let holes = (0..4).map(|_| pool.alloc(-1)).collect::<Vec<_>>();
+    let keep = pool.alloc(0);
+    for p in holes {
+        pool.free(p);
+    }
+    let cp = pool.checkpoint();
+    pool.alloc(1);
+    pool.alloc(2);
+    let map = pool.compact_vacant_to_front();
+    assert!(!map.is_empty());
+    assert_eq!(pool.rollback(cp), 2);
+    assert_eq!(pool.len(), 1);
+    assert_eq!(pool[*map.get(&keep).unwrap_or(&keep)], 0);
+    pool.assert_consistent();
+}
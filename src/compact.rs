@@ -0,0 +1,146 @@
+use crate::{BlockStorage, Entry, Pool, Ptr};
+use core::ptr::NonNull;
+use std::collections::HashMap;
+
+/// A value which holds pointers to be updated after `Pool::compact`.
+pub trait Remap<T> {
+    /// Replaces every pointer contained in the map with the corresponding one.
+    fn remap(&mut self, map: &HashMap<Ptr<T>, Ptr<T>>);
+}
+
+impl<T> Remap<T> for Ptr<T> {
+    fn remap(&mut self, map: &HashMap<Ptr<T>, Ptr<T>>) {
+        if let Some(&p) = map.get(self) {
+            *self = p;
+        }
+    }
+}
+
+impl<T, R: Remap<T>> Remap<T> for Option<R> {
+    fn remap(&mut self, map: &HashMap<Ptr<T>, Ptr<T>>) {
+        if let Some(r) = self {
+            r.remap(map);
+        }
+    }
+}
+
+impl<T, R: Remap<T>> Remap<T> for Vec<R> {
+    fn remap(&mut self, map: &HashMap<Ptr<T>, Ptr<T>>) {
+        self.iter_mut().for_each(|r| r.remap(map));
+    }
+}
+
+impl<T, S: BlockStorage> Pool<T, S> {
+    /// Moves the occupied entries into the fewest blocks, preferring larger and fuller blocks to keep,
+    /// and releases the other blocks. Returns the mapping from the old pointers to the new ones of the moved entries;
+    /// entries which are not moved are not contained in it.
+    ///
+    /// # Safety
+    /// The memory of the released blocks is deallocated, so no pointer to an entry in them may be passed
+    /// to this pool afterwards. Every such pointer to an occupied entry must be replaced by the mapping.
+    ///
+    /// # Panics
+    /// Panics if the blocks of the storage are not `BlockStorage::REORDERABLE`.
+    pub unsafe fn compact(&mut self) -> HashMap<Ptr<T>, Ptr<T>> {
+        assert!(S::REORDERABLE, "blocks of this storage cannot be released");
+        let occupied = |block: &[Entry<T>]| block.iter().filter(|e| e.is_occupied()).count();
+        let mut order = (0..self.blocks.len()).collect::<Vec<_>>();
+        order.sort_by_key(|&i| {
+            let block = &self.blocks[i];
+            core::cmp::Reverse((block.len(), occupied(block)))
+        });
+        let mut keep = vec![false; self.blocks.len()];
+        let mut capacity = 0;
+        for i in order {
+            if capacity >= self.len {
+                break;
+            }
+            keep[i] = true;
+            capacity += self.blocks[i].len();
+        }
+
+        let (mut kept, mut released) = (Vec::new(), Vec::new());
+        for (block, &keep) in self.blocks.iter_mut().zip(&keep) {
+            let slots = block.iter_mut().map(NonNull::from);
+            if keep {
+                kept.extend(slots.filter(|slot| !slot.as_ref().is_occupied()));
+            } else {
+                released.extend(slots.filter(|slot| slot.as_ref().is_occupied()));
+            }
+        }
+        let mut remap = HashMap::new();
+        for (src, dst) in released.into_iter().zip(kept) {
+            let (src, dst) = (&mut *src.as_ptr(), &mut *dst.as_ptr());
+            let old = Ptr::to(src, self.id);
+            dst.occupy(src.vacate(None).unwrap());
+            remap.insert(old, Ptr::to(dst, self.id));
+        }
+
+        let mut keep = keep.into_iter();
+        self.blocks.retain(|_| keep.next().unwrap());
+        self.blocks.shrink_to_fit();
+        self.reindex_blocks();
+        self.rebuild_free_list();
+        if let Some(log) = &mut self.log {
+            log.remap(&remap);
+        }
+        #[cfg(feature = "debug-checks")]
+        for (&old, &new) in &remap {
+            self.tags.moved(old, new);
+        }
+        self.forget_released_blocks();
+        remap
+    }
+}
+
+#[test]
+fn test() {
+    use crate::{GrowthPolicy, PoolBuilder};
+
+    struct Node {
+        value: usize,
+        next: Option<Ptr<Node>>,
+    }
+    impl Remap<Node> for Node {
+        fn remap(&mut self, map: &HashMap<Ptr<Node>, Ptr<Node>>) {
+            self.next.remap(map);
+        }
+    }
+
+    let mut pool = PoolBuilder::new()
+        .block_size(2)
+        .growth(GrowthPolicy::Doubling)
+        .build();
+    let mut ptrs = (0..14)
+        .map(|value| pool.alloc(Node { value, next: None }))
+        .collect::<Vec<_>>();
+    assert_eq!(pool.iter_blocks().count(), 3);
+    for i in 0..13 {
+        pool[ptrs[i]].next = Some(ptrs[i + 1]);
+    }
+    // keeps 0, 1 and 13, which are far apart
+    for &p in &ptrs[2..13] {
+        pool.free(p);
+    }
+    pool[ptrs[1]].next = Some(ptrs[13]);
+    let block_of = |pool: &Pool<Node>, p: Ptr<Node>| pool.ptr_to_repr(p).unwrap().block;
+    assert_ne!(block_of(&pool, ptrs[0]), block_of(&pool, ptrs[13]));
+
+    let map = unsafe { pool.compact() };
+    assert_eq!(pool.iter_blocks().count(), 1);
+    assert_eq!(pool.capacity(), 8);
+    assert_eq!(map.len(), 2);
+    ptrs.remap(&map);
+    pool.iter_mut().for_each(|(_, node)| node.remap(&map));
+    pool.assert_consistent();
+    assert_eq!(pool.len(), 3);
+    assert_eq!(pool[ptrs[0]].next, Some(ptrs[1]));
+    assert_eq!(pool[ptrs[1]].next, Some(ptrs[13]));
+    assert_eq!(pool[ptrs[13]].value, 13);
+
+    let mut empty = Pool::<i32>::with_block_size(4);
+    let p = empty.alloc(0);
+    empty.free(p);
+    assert!(unsafe { empty.compact() }.is_empty());
+    assert_eq!(empty.capacity(), 0);
+}
@@ -0,0 +1,586 @@
+use crate::PoolId;
+use std::cell::UnsafeCell;
+use std::marker::PhantomData;
+use std::mem::MaybeUninit;
+use std::ops::Deref;
+use std::sync::atomic::{fence, AtomicPtr, AtomicU32, AtomicU64, AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+/// The number of slots of the first segment. The `k`-th segment has `BASE << k` slots.
+const BASE: usize = 1024;
+/// The number of segments, with which the total number of slots just fits in `u32`.
+const SEGMENTS: usize = 22;
+
+// The state of a slot is `generation << 32 | readers << 1 | occupied`.
+const OCCUPIED: u64 = 1;
+const READER: u64 = 2;
+const READERS: u64 = 0xffff_fffe;
+
+fn generation(state: u64) -> u32 {
+    (state >> 32) as u32
+}
+
+fn readers(state: u64) -> u64 {
+    (state & READERS) >> 1
+}
+
+/// Returns the segment and the offset in it of the `index`-th slot.
+fn locate(index: u32) -> (usize, usize) {
+    let j = index as usize / BASE + 1;
+    let k = (usize::BITS - 1 - j.leading_zeros()) as usize;
+    (k, index as usize - BASE * ((1 << k) - 1))
+}
+
+struct Slot<T> {
+    state: AtomicU64,
+    /// The next slot in the free list, as `index + 1`, or `0` for the end of the list.
+    next: AtomicU32,
+    value: UnsafeCell<MaybeUninit<T>>,
+}
+
+impl<T> Slot<T> {
+    fn new() -> Self {
+        Self {
+            state: AtomicU64::new(0),
+            next: AtomicU32::new(0),
+            value: UnsafeCell::new(MaybeUninit::uninit()),
+        }
+    }
+}
+
+/// A thread-safe memory pool whose `alloc` and `free` take `&self`.
+///
+/// The slots are stored in segments which never move, and the vacant slots form
+/// a lock-free Treiber stack whose head is tagged against the ABA problem.
+/// A lock is taken only when the pool grows by a new segment.
+///
+/// A value freed while `ConcurrentRef`s to it are alive is dropped when the last of them is dropped.
+///
+/// Alternatively, readers can pin an epoch by `pin`, and read values through the `EpochGuard` without counting.
+/// A value freed while guards are pinned is retired, and dropped once every guard which could refer to it is dropped:
+/// the global epoch advances only when no guard is pinned at the previous epoch,
+/// so a value retired at epoch `e` is unreachable when the epoch reaches `e + 2`.
+pub struct ConcurrentPool<T> {
+    segments: [AtomicPtr<Slot<T>>; SEGMENTS],
+    /// The number of allocated segments, locked while growing.
+    grown: Mutex<usize>,
+    /// The head of the free list, as `tag << 32 | (index + 1)`.
+    head: AtomicU64,
+    id: PoolId,
+    len: AtomicUsize,
+    epoch: AtomicU64,
+    /// The numbers of the guards pinned at even and odd epochs.
+    pinned: [AtomicUsize; 2],
+    /// The freed slots whose values cannot be dropped yet, with the epochs at which they are retired.
+    retired: Mutex<Vec<(u64, u32)>>,
+}
+
+unsafe impl<T: Send> Send for ConcurrentPool<T> {}
+unsafe impl<T: Send + Sync> Sync for ConcurrentPool<T> {}
+
+impl<T> ConcurrentPool<T> {
+    pub fn new() -> Self {
+        Self {
+            segments: Default::default(),
+            grown: Mutex::new(0),
+            head: AtomicU64::new(0),
+            id: PoolId::gen(),
+            len: AtomicUsize::new(0),
+            epoch: AtomicU64::new(0),
+            pinned: Default::default(),
+            retired: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn id(&self) -> PoolId {
+        self.id
+    }
+
+    /// Number of occupied slots, which may be outdated as soon as it is returned.
+    /// A freed value is not counted even if it is dropped later, after it is read.
+    pub fn len(&self) -> usize {
+        self.len.load(Ordering::Relaxed)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Total number of slots (both vacant and occupied).
+    pub fn capacity(&self) -> usize {
+        BASE * ((1 << *self.grown.lock().unwrap()) - 1)
+    }
+
+    fn slot(&self, index: u32) -> &Slot<T> {
+        let (k, offset) = locate(index);
+        unsafe { &*self.segments[k].load(Ordering::Acquire).add(offset) }
+    }
+
+    fn pop(&self) -> Option<u32> {
+        let mut head = self.head.load(Ordering::Acquire);
+        loop {
+            let top = head as u32;
+            if top == 0 {
+                return None;
+            }
+            let next = self.slot(top - 1).next.load(Ordering::Relaxed);
+            let tag = generation(head).wrapping_add(1);
+            let new = (tag as u64) << 32 | next as u64;
+            match self
+                .head
+                .compare_exchange_weak(head, new, Ordering::AcqRel, Ordering::Acquire)
+            {
+                Ok(_) => return Some(top - 1),
+                Err(current) => head = current,
+            }
+        }
+    }
+
+    /// Pushes the slots from `first` to `last`, which are already linked, onto the free list.
+    fn push(&self, first: u32, last: u32) {
+        let mut head = self.head.load(Ordering::Relaxed);
+        loop {
+            self.slot(last).next.store(head as u32, Ordering::Relaxed);
+            let tag = generation(head).wrapping_add(1);
+            let new = (tag as u64) << 32 | (first + 1) as u64;
+            match self
+                .head
+                .compare_exchange_weak(head, new, Ordering::Release, Ordering::Relaxed)
+            {
+                Ok(_) => return,
+                Err(current) => head = current,
+            }
+        }
+    }
+
+    fn grow(&self) {
+        let mut grown = self.grown.lock().unwrap();
+        if self.head.load(Ordering::Acquire) as u32 != 0 {
+            return; // another thread has already grown the pool
+        }
+        let k = *grown;
+        assert!(k < SEGMENTS, "concurrent pool {:?} is exhausted", self.id);
+        let size = BASE << k;
+        let segment = (0..size).map(|_| Slot::<T>::new()).collect::<Box<[_]>>();
+        let segment = Box::into_raw(segment) as *mut Slot<T>;
+        self.segments[k].store(segment, Ordering::Release);
+        let first = (BASE * ((1 << k) - 1)) as u32;
+        for i in 0..size as u32 - 1 {
+            self.slot(first + i)
+                .next
+                .store(first + i + 2, Ordering::Relaxed);
+        }
+        self.push(first, first + size as u32 - 1);
+        *grown += 1;
+    }
+
+    pub fn alloc(&self, value: T) -> ConcurrentPtr<T> {
+        let index = loop {
+            match self.pop() {
+                Some(index) => break index,
+                None => self.grow(),
+            }
+        };
+        let slot = self.slot(index);
+        unsafe { (*slot.value.get()).as_mut_ptr().write(value) };
+        let generation = generation(slot.state.load(Ordering::Relaxed));
+        slot.state
+            .store((generation as u64) << 32 | OCCUPIED, Ordering::Release);
+        self.len.fetch_add(1, Ordering::Relaxed);
+        ConcurrentPtr {
+            index,
+            generation,
+            pool_id: self.id,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Frees the slot of `p`. If `ConcurrentRef`s to it are alive, the value is dropped by the last of them.
+    /// Returns `false` if the slot is already freed.
+    pub fn free(&self, p: ConcurrentPtr<T>) -> bool {
+        assert!(p.pool_id == self.id);
+        let slot = self.slot(p.index);
+        let mut state = slot.state.load(Ordering::Acquire);
+        loop {
+            if state & OCCUPIED == 0 || generation(state) != p.generation {
+                return false;
+            }
+            let new = (p.generation.wrapping_add(1) as u64) << 32 | (state & READERS);
+            match slot
+                .state
+                .compare_exchange_weak(state, new, Ordering::AcqRel, Ordering::Acquire)
+            {
+                Ok(_) => break,
+                Err(current) => state = current,
+            }
+        }
+        self.len.fetch_sub(1, Ordering::Relaxed);
+        if readers(state) == 0 {
+            self.release(p.index);
+        }
+        true
+    }
+
+    /// Reclaims the freed slot which no `ConcurrentRef` refers to, or retires it if any guard is pinned.
+    fn release(&self, index: u32) {
+        fence(Ordering::SeqCst);
+        if self.pinned.iter().all(|n| n.load(Ordering::SeqCst) == 0) {
+            self.reclaim(index);
+        } else {
+            let epoch = self.epoch.load(Ordering::SeqCst);
+            self.retired.lock().unwrap().push((epoch, index));
+            self.collect();
+        }
+    }
+
+    /// Pins the current epoch, during which the values read through the guard are not dropped.
+    pub fn pin(&self) -> EpochGuard<'_, T> {
+        loop {
+            let epoch = self.epoch.load(Ordering::SeqCst);
+            self.pinned[epoch as usize % 2].fetch_add(1, Ordering::SeqCst);
+            if self.epoch.load(Ordering::SeqCst) == epoch {
+                fence(Ordering::SeqCst);
+                return EpochGuard { pool: self, epoch };
+            }
+            self.pinned[epoch as usize % 2].fetch_sub(1, Ordering::SeqCst);
+        }
+    }
+
+    /// Advances the epoch if possible, and reclaims the retired slots which no guard can refer to anymore.
+    /// This is called whenever a slot is retired or a guard is dropped.
+    pub fn collect(&self) {
+        let mut epoch = self.epoch.load(Ordering::SeqCst);
+        for _ in 0..2 {
+            if self.pinned[(epoch as usize + 1) % 2].load(Ordering::SeqCst) != 0 {
+                break;
+            }
+            epoch = match self.epoch.compare_exchange(
+                epoch,
+                epoch + 1,
+                Ordering::SeqCst,
+                Ordering::SeqCst,
+            ) {
+                Ok(_) => epoch + 1,
+                Err(current) => current,
+            };
+        }
+        let mut ready = Vec::new();
+        match self.retired.try_lock() {
+            Ok(mut retired) => retired.retain(|&(retired, index)| {
+                let keep = retired + 2 > epoch;
+                if !keep {
+                    ready.push(index);
+                }
+                keep
+            }),
+            Err(_) => return,
+        }
+        for index in ready {
+            self.reclaim(index);
+        }
+    }
+
+    /// Drops the value of the freed slot and pushes the slot onto the free list.
+    fn reclaim(&self, index: u32) {
+        unsafe { std::ptr::drop_in_place((*self.slot(index).value.get()).as_mut_ptr()) };
+        self.push(index, index);
+    }
+
+    pub fn get(&self, p: ConcurrentPtr<T>) -> Option<ConcurrentRef<'_, T>> {
+        assert!(p.pool_id == self.id);
+        let slot = self.slot(p.index);
+        let mut state = slot.state.load(Ordering::Acquire);
+        loop {
+            if state & OCCUPIED == 0 || generation(state) != p.generation {
+                return None;
+            }
+            assert!(readers(state) < READERS >> 1, "too many readers");
+            match slot.state.compare_exchange_weak(
+                state,
+                state + READER,
+                Ordering::Acquire,
+                Ordering::Acquire,
+            ) {
+                Ok(_) => break,
+                Err(current) => state = current,
+            }
+        }
+        Some(ConcurrentRef {
+            pool: self,
+            index: p.index,
+        })
+    }
+
+    pub fn get_mut(&mut self, p: ConcurrentPtr<T>) -> Option<&mut T> {
+        assert!(p.pool_id == self.id);
+        let slot = self.slot(p.index);
+        let state = slot.state.load(Ordering::Relaxed);
+        if state & OCCUPIED == 0 || generation(state) != p.generation {
+            return None;
+        }
+        Some(unsafe { &mut *(*slot.value.get()).as_mut_ptr() })
+    }
+}
+
+impl<T> Default for ConcurrentPool<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Drop for ConcurrentPool<T> {
+    fn drop(&mut self) {
+        for (_, index) in std::mem::take(self.retired.get_mut().unwrap()) {
+            unsafe { std::ptr::drop_in_place((*self.slot(index).value.get()).as_mut_ptr()) };
+        }
+        for k in 0..*self.grown.get_mut().unwrap() {
+            let size = BASE << k;
+            let segment = *self.segments[k].get_mut();
+            let segment =
+                unsafe { Box::from_raw(std::ptr::slice_from_raw_parts_mut(segment, size)) };
+            for slot in segment.iter() {
+                if slot.state.load(Ordering::Relaxed) & OCCUPIED != 0 {
+                    unsafe { std::ptr::drop_in_place((*slot.value.get()).as_mut_ptr()) };
+                }
+            }
+        }
+    }
+}
+
+impl<T> std::fmt::Debug for ConcurrentPool<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("ConcurrentPool")
+            .field("id", &self.id)
+            .field("len", &self.len())
+            .finish()
+    }
+}
+
+/// A handle to a value in `ConcurrentPool`, which can be sent to other threads.
+pub struct ConcurrentPtr<T> {
+    index: u32,
+    generation: u32,
+    pool_id: PoolId,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T> std::fmt::Debug for ConcurrentPtr<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "ConcurrentPtr {{ index: {}, generation: {}, pool_id: {:?} }}",
+            self.index, self.generation, self.pool_id
+        )
+    }
+}
+impl<T> Clone for ConcurrentPtr<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+impl<T> Copy for ConcurrentPtr<T> {}
+impl<T> PartialEq for ConcurrentPtr<T> {
+    fn eq(&self, rhs: &Self) -> bool {
+        (self.index, self.generation, self.pool_id) == (rhs.index, rhs.generation, rhs.pool_id)
+    }
+}
+impl<T> Eq for ConcurrentPtr<T> {}
+impl<T> std::hash::Hash for ConcurrentPtr<T> {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        (self.index, self.generation, self.pool_id).hash(state)
+    }
+}
+
+/// A shared reference to a value in `ConcurrentPool`, which keeps the value alive even if it is freed meanwhile.
+pub struct ConcurrentRef<'a, T> {
+    pool: &'a ConcurrentPool<T>,
+    index: u32,
+}
+
+impl<'a, T> Deref for ConcurrentRef<'a, T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        unsafe { &*(*self.pool.slot(self.index).value.get()).as_ptr() }
+    }
+}
+
+impl<'a, T> Drop for ConcurrentRef<'a, T> {
+    fn drop(&mut self) {
+        let state = self
+            .pool
+            .slot(self.index)
+            .state
+            .fetch_sub(READER, Ordering::AcqRel);
+        if state & OCCUPIED == 0 && readers(state) == 1 {
+            self.pool.release(self.index);
+        }
+    }
+}
+
+/// A pinned epoch of `ConcurrentPool`, created by `ConcurrentPool::pin`.
+/// The references returned by `get` stay valid while this is alive, even if the values are freed meanwhile.
+pub struct EpochGuard<'a, T> {
+    pool: &'a ConcurrentPool<T>,
+    epoch: u64,
+}
+
+impl<'a, T> EpochGuard<'a, T> {
+    pub fn get(&self, p: ConcurrentPtr<T>) -> Option<&T> {
+        assert!(p.pool_id == self.pool.id);
+        let slot = self.pool.slot(p.index);
+        let state = slot.state.load(Ordering::SeqCst);
+        if state & OCCUPIED == 0 || generation(state) != p.generation {
+            return None;
+        }
+        Some(unsafe { &*(*slot.value.get()).as_ptr() })
+    }
+}
+
+impl<'a, T> Drop for EpochGuard<'a, T> {
+    fn drop(&mut self) {
+        self.pool.pinned[self.epoch as usize % 2].fetch_sub(1, Ordering::SeqCst);
+        self.pool.collect();
+    }
+}
+
+impl<'a, T> std::fmt::Debug for EpochGuard<'a, T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "EpochGuard({})", self.epoch)
+    }
+}
+
+impl<'a, T: std::fmt::Debug> std::fmt::Debug for ConcurrentRef<'a, T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+#[test]
+fn test_locate() {
+    assert_eq!(locate(0), (0, 0));
+    assert_eq!(locate(BASE as u32 - 1), (0, BASE - 1));
+    assert_eq!(locate(BASE as u32), (1, 0));
+    assert_eq!(locate(3 * BASE as u32), (2, 0));
+    let last = BASE * ((1 << SEGMENTS) - 1) - 1;
+    assert_eq!(
+        locate(last as u32),
+        (SEGMENTS - 1, (BASE << (SEGMENTS - 1)) - 1)
+    );
+}
+
+#[test]
+fn alloc_and_free() {
+    let mut pool = ConcurrentPool::new();
+    let p = pool.alloc(String::from("a"));
+    assert_eq!(*pool.get(p).unwrap(), "a");
+    pool.get_mut(p).unwrap().push('b');
+    assert_eq!(*pool.get(p).unwrap(), "ab");
+    assert!(pool.free(p));
+    assert!(!pool.free(p));
+    assert!(pool.get(p).is_none());
+    let q = pool.alloc(String::from("c"));
+    assert_ne!(p, q);
+    assert!(pool.get(p).is_none());
+    assert_eq!(pool.len(), 1);
+}
+
+#[test]
+fn free_while_reading() {
+    use std::rc::Rc;
+    let pool = ConcurrentPool::new();
+    let value = Rc::new(());
+    let p = pool.alloc(value.clone());
+    let r = pool.get(p).unwrap();
+    assert!(pool.free(p));
+    assert!(pool.get(p).is_none());
+    assert_eq!(Rc::strong_count(&value), 2);
+    assert!(pool.is_empty());
+    drop(r);
+    assert_eq!(Rc::strong_count(&value), 1);
+}
+
+#[test]
+fn threads() {
+    let pool = ConcurrentPool::new();
+    std::thread::scope(|s| {
+        for t in 0..4 {
+            let pool = &pool;
+            s.spawn(move || {
+                let mut ptrs = Vec::new();
+                for i in 0..3000 {
+                    ptrs.push((pool.alloc(t * 10000 + i), t * 10000 + i));
+                    if i % 3 == 0 {
+                        let (p, _) = ptrs.swap_remove(ptrs.len() / 2);
+                        assert!(pool.free(p));
+                    }
+                }
+                for &(p, value) in &ptrs {
+                    assert_eq!(*pool.get(p).unwrap(), value);
+                }
+            });
+        }
+    });
+    assert_eq!(pool.len(), 4 * 2000);
+    assert!(pool.capacity() >= pool.len());
+}
+
+#[test]
+fn epoch() {
+    use std::rc::Rc;
+    let pool = ConcurrentPool::new();
+    let value = Rc::new(());
+    let p = pool.alloc(value.clone());
+    let guard = pool.pin();
+    let r = guard.get(p).unwrap();
+    assert!(pool.free(p));
+    assert!(guard.get(p).is_none());
+    assert_eq!(Rc::strong_count(r), 2);
+    let q = pool.alloc(value.clone());
+    assert_ne!(p.index, q.index);
+    // a guard pinned after `free` cannot refer to the freed value, so it does not keep it
+    let guard2 = pool.pin();
+    assert_eq!(Rc::strong_count(&value), 3);
+    drop(guard);
+    assert_eq!(Rc::strong_count(&value), 2);
+    drop(guard2);
+    assert_eq!(pool.len(), 1);
+
+    // a leaked guard keeps the value retired until the pool is dropped
+    std::mem::forget(pool.pin());
+    assert!(pool.free(q));
+    pool.collect();
+    assert_eq!(Rc::strong_count(&value), 2);
+    drop(pool);
+    assert_eq!(Rc::strong_count(&value), 1);
+}
+
+#[test]
+fn epoch_threads() {
+    let pool = ConcurrentPool::new();
+    let ptrs = (0..64).map(|i| pool.alloc(vec![i; 16])).collect::<Vec<_>>();
+    let ptrs = std::sync::Mutex::new(ptrs);
+    std::thread::scope(|s| {
+        for _ in 0..3 {
+            s.spawn(|| {
+                for _ in 0..2000 {
+                    let snapshot = ptrs.lock().unwrap().clone();
+                    let guard = pool.pin();
+                    for p in snapshot {
+                        if let Some(v) = guard.get(p) {
+                            assert!(v.iter().all(|&x| x == v[0]));
+                        }
+                    }
+                }
+            });
+        }
+        s.spawn(|| {
+            for i in 0..2000 {
+                let mut ptrs = ptrs.lock().unwrap();
+                let j = i % ptrs.len();
+                assert!(pool.free(ptrs[j]));
+                ptrs[j] = pool.alloc(vec![i; 16]);
+            }
+        });
+    });
+    assert_eq!(pool.len(), 64);
+}
@@ -0,0 +1,299 @@
+use crate::{Pool, Ptr};
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use core::any::{Any, TypeId};
+use core::marker::PhantomData;
+
+/// The conversions of a reference to `T` to the ones to `D`, typically a trait object implemented by `T`.
+/// Made by `coerce!()`, which lets the compiler unsize the references.
+pub struct Coerce<T, D: ?Sized> {
+    as_ref: fn(&T) -> &D,
+    as_mut: fn(&mut T) -> &mut D,
+}
+
+impl<T, D: ?Sized> Coerce<T, D> {
+    pub fn new(as_ref: fn(&T) -> &D, as_mut: fn(&mut T) -> &mut D) -> Self {
+        Self { as_ref, as_mut }
+    }
+}
+
+impl<T, D: ?Sized> Clone for Coerce<T, D> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+impl<T, D: ?Sized> Copy for Coerce<T, D> {}
+
+/// Makes the `Coerce` of the unsizing coercion, e.g. for `DynPool::alloc`.
+#[macro_export]
+macro_rules! coerce {
+    () => {
+        $crate::Coerce::new(|value| value, |value| value)
+    };
+}
+
+/// A pointer to a value in `DynPool<D>`, which is accessed as `D` without knowing its concrete type.
+pub struct DynPtr<D: ?Sized> {
+    type_id: TypeId,
+    ptr: Ptr<()>,
+    _dyn: PhantomData<fn(&D)>,
+}
+
+impl<D: ?Sized> DynPtr<D> {
+    pub fn is<T: 'static>(&self) -> bool {
+        self.type_id == TypeId::of::<T>()
+    }
+
+    pub fn downcast<T: 'static>(self) -> Option<Ptr<T>> {
+        self.is::<T>().then(|| self.ptr.cast())
+    }
+}
+
+/// The pool of a concrete type `T` in `DynPool<D>`, with the coercion of its values to `D`.
+struct Typed<T, D: ?Sized> {
+    pool: Pool<T>,
+    coerce: Coerce<T, D>,
+}
+
+trait ErasedPool<D: ?Sized> {
+    fn get(&self, p: Ptr<()>) -> Option<&D>;
+    fn get_mut(&mut self, p: Ptr<()>) -> Option<&mut D>;
+    fn free(&mut self, p: Ptr<()>) -> bool;
+    fn len(&self) -> usize;
+    fn iter(&self) -> Box<dyn Iterator<Item = (Ptr<()>, &D)> + '_>;
+    fn iter_mut(&mut self) -> Box<dyn Iterator<Item = (Ptr<()>, &mut D)> + '_>;
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+impl<T: 'static, D: ?Sized + 'static> ErasedPool<D> for Typed<T, D> {
+    fn get(&self, p: Ptr<()>) -> Option<&D> {
+        let value = self.pool.get(p.cast())?.get();
+        Some((self.coerce.as_ref)(value))
+    }
+    fn get_mut(&mut self, p: Ptr<()>) -> Option<&mut D> {
+        let value = self.pool.get_mut(p.cast())?;
+        Some((self.coerce.as_mut)(value))
+    }
+    fn free(&mut self, p: Ptr<()>) -> bool {
+        self.pool.free(p.cast())
+    }
+    fn len(&self) -> usize {
+        self.pool.len()
+    }
+    fn iter(&self) -> Box<dyn Iterator<Item = (Ptr<()>, &D)> + '_> {
+        let as_ref = self.coerce.as_ref;
+        Box::new(
+            self.pool
+                .iter()
+                .map(move |(p, value)| (p.cast(), as_ref(value))),
+        )
+    }
+    fn iter_mut(&mut self) -> Box<dyn Iterator<Item = (Ptr<()>, &mut D)> + '_> {
+        let as_mut = self.coerce.as_mut;
+        Box::new(
+            self.pool
+                .iter_mut()
+                .map(move |(p, value)| (p.cast(), as_mut(value))),
+        )
+    }
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+/// A pool of values of different types accessed as `D`, typically `dyn Trait` of a trait implemented by them.
+/// Like `AnyPool`, this holds a `Pool<T>` for each concrete type `T`, so the values are not boxed one by one.
+pub struct DynPool<D: ?Sized> {
+    pools: BTreeMap<TypeId, Box<dyn ErasedPool<D>>>,
+}
+
+impl<D: ?Sized + 'static> DynPool<D> {
+    pub fn new() -> Self {
+        Self {
+            pools: BTreeMap::new(),
+        }
+    }
+
+    /// Number of values of all types.
+    pub fn len(&self) -> usize {
+        self.pools.values().map(|pool| pool.len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the pool of `T`, or `None` if no value of `T` has been allocated.
+    pub fn pool<T: 'static>(&self) -> Option<&Pool<T>> {
+        let pool = self.pools.get(&TypeId::of::<T>())?;
+        let typed: &Typed<T, D> = pool.as_any().downcast_ref()?;
+        Some(&typed.pool)
+    }
+
+    /// Allocates `value`, which is accessed as `D` by `coerce`, usually `coerce!()`.
+    /// The coercion of the first value of `T` is used for all values of `T`.
+    pub fn alloc<T: 'static>(&mut self, value: T, coerce: Coerce<T, D>) -> DynPtr<D> {
+        let pool = self.pools.entry(TypeId::of::<T>()).or_insert_with(|| {
+            Box::new(Typed {
+                pool: Pool::<T>::new(),
+                coerce,
+            })
+        });
+        let typed: &mut Typed<T, D> = pool.as_any_mut().downcast_mut().unwrap();
+        DynPtr {
+            type_id: TypeId::of::<T>(),
+            ptr: typed.pool.alloc(value).cast(),
+            _dyn: PhantomData,
+        }
+    }
+
+    /// Returns `None` if `p` is already freed.
+    pub fn get(&self, p: DynPtr<D>) -> Option<&D> {
+        self.pools.get(&p.type_id)?.get(p.ptr)
+    }
+
+    pub fn get_mut(&mut self, p: DynPtr<D>) -> Option<&mut D> {
+        self.pools.get_mut(&p.type_id)?.get_mut(p.ptr)
+    }
+
+    pub fn free(&mut self, p: DynPtr<D>) -> bool {
+        match self.pools.get_mut(&p.type_id) {
+            Some(pool) => pool.free(p.ptr),
+            None => false,
+        }
+    }
+
+    /// Moves the value out, or returns `None` if `p` is freed or not of `T`.
+    pub fn take<T: 'static>(&mut self, p: DynPtr<D>) -> Option<T> {
+        let p = p.downcast()?;
+        let pool = self.pools.get_mut(&TypeId::of::<T>())?;
+        let typed: &mut Typed<T, D> = pool.as_any_mut().downcast_mut()?;
+        typed.pool.take(p)
+    }
+
+    /// Iterates over the values grouped by their types.
+    pub fn iter(&self) -> impl Iterator<Item = (DynPtr<D>, &D)> {
+        self.pools.iter().flat_map(|(&type_id, pool)| {
+            pool.iter().map(move |(ptr, value)| {
+                let p = DynPtr {
+                    type_id,
+                    ptr,
+                    _dyn: PhantomData,
+                };
+                (p, value)
+            })
+        })
+    }
+
+    /// Iterates over the values grouped by their types.
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (DynPtr<D>, &mut D)> {
+        self.pools.iter_mut().flat_map(|(&type_id, pool)| {
+            pool.iter_mut().map(move |(ptr, value)| {
+                let p = DynPtr {
+                    type_id,
+                    ptr,
+                    _dyn: PhantomData,
+                };
+                (p, value)
+            })
+        })
+    }
+}
+
+impl<D: ?Sized + 'static> Default for DynPool<D> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<D: ?Sized + 'static> core::fmt::Debug for DynPool<D> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(f, "DynPool {{ len: {} }}", self.len())
+    }
+}
+
+impl<D: ?Sized> core::fmt::Debug for DynPtr<D> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(f, "DynPtr({:?}, {:?})", self.type_id, self.ptr)
+    }
+}
+impl<D: ?Sized> Clone for DynPtr<D> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+impl<D: ?Sized> Copy for DynPtr<D> {}
+impl<D: ?Sized> PartialEq for DynPtr<D> {
+    fn eq(&self, rhs: &Self) -> bool {
+        self.type_id == rhs.type_id && self.ptr == rhs.ptr
+    }
+}
+impl<D: ?Sized> Eq for DynPtr<D> {}
+impl<D: ?Sized> core::hash::Hash for DynPtr<D> {
+    fn hash<H: core::hash::Hasher>(&self, state: &mut H) {
+        self.type_id.hash(state);
+        self.ptr.hash(state);
+    }
+}
+
+#[test]
+fn test() {
+    use alloc::string::String;
+    use alloc::vec::Vec;
+
+    trait Widget {
+        fn draw(&self) -> String;
+        fn resize(&mut self, width: u32);
+    }
+    struct Button(u32);
+    struct Label(String, u32);
+    impl Widget for Button {
+        fn draw(&self) -> String {
+            alloc::format!("[button {}]", self.0)
+        }
+        fn resize(&mut self, width: u32) {
+            self.0 = width;
+        }
+    }
+    impl Widget for Label {
+        fn draw(&self) -> String {
+            alloc::format!("{} {}", self.0, self.1)
+        }
+        fn resize(&mut self, width: u32) {
+            self.1 = width;
+        }
+    }
+
+    let mut pool = DynPool::<dyn Widget>::new();
+    let button = pool.alloc(Button(10), coerce!());
+    let label = pool.alloc(Label(String::from("name"), 20), coerce!());
+    let other = pool.alloc(Button(30), coerce!());
+    assert_eq!(pool.len(), 3);
+    assert!(button.is::<Button>() && label.is::<Label>());
+    assert_eq!(pool.get(label).unwrap().draw(), "name 20");
+    pool.get_mut(button).unwrap().resize(15);
+    assert_eq!(pool.get(button).unwrap().draw(), "[button 15]");
+    pool.iter_mut().for_each(|(_, w)| w.resize(1));
+    let mut drawn = pool.iter().map(|(_, w)| w.draw()).collect::<Vec<_>>();
+    drawn.sort();
+    assert_eq!(drawn, ["[button 1]", "[button 1]", "name 1"]);
+    assert_eq!(pool.pool::<Button>().unwrap().len(), 2);
+    assert_eq!(pool.take::<Button>(label).map(|b| b.0), None);
+    assert_eq!(
+        pool.take::<Label>(label).map(|l| l.0).as_deref(),
+        Some("name")
+    );
+    assert!(pool.free(other) && !pool.free(other));
+    assert!(pool.get(other).is_none() && pool.get(label).is_none());
+    assert_eq!(pool.len(), 1);
+    assert_eq!(
+        button
+            .downcast()
+            .map(|p| pool.pool::<Button>().unwrap()[p].0),
+        Some(1)
+    );
+}
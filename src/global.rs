@@ -0,0 +1,154 @@
+use crate::{Pool, Ptr};
+use std::ops::Deref;
+use std::sync::{Mutex, MutexGuard, OnceLock};
+
+/// The pool of `StaticPool`, which is created without an observer and never given one,
+/// so it holds only `T` and its own blocks.
+struct Inner<T>(Pool<T>);
+
+unsafe impl<T: Send> Send for Inner<T> {}
+
+/// A pool which can be declared in a `static`, created on the first lock and shared by threads with a lock.
+/// `static_pool!` declares one.
+///
+/// The guard gives the pool by `Deref` for reading, and the methods to allocate and free,
+/// but not `&mut Pool`, since an observer set to the shared pool might be called on another thread.
+pub struct StaticPool<T> {
+    pool: OnceLock<Mutex<Inner<T>>>,
+    block_size: usize,
+}
+
+impl<T> StaticPool<T> {
+    pub const fn new() -> Self {
+        Self::with_block_size(Pool::<T>::DEFAULT_BLOCK_SIZE)
+    }
+
+    /// Creates a pool which will have blocks of `block_size` entries.
+    pub const fn with_block_size(block_size: usize) -> Self {
+        Self {
+            pool: OnceLock::new(),
+            block_size,
+        }
+    }
+
+    /// Locks the pool, creating it on the first call.
+    ///
+    /// # Panics
+    /// Panics if another thread panicked while holding the lock.
+    pub fn lock(&self) -> StaticPoolGuard<'_, T> {
+        let pool = self
+            .pool
+            .get_or_init(|| Mutex::new(Inner(Pool::with_block_size(self.block_size))));
+        StaticPoolGuard {
+            guard: pool.lock().unwrap(),
+        }
+    }
+}
+
+impl<T> Default for StaticPool<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> std::fmt::Debug for StaticPool<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "StaticPool {{ block_size: {} }}", self.block_size)
+    }
+}
+
+/// The lock of `StaticPool`, by which the pool is used.
+pub struct StaticPoolGuard<'a, T> {
+    guard: MutexGuard<'a, Inner<T>>,
+}
+
+impl<'a, T> StaticPoolGuard<'a, T> {
+    pub fn alloc(&mut self, value: T) -> Ptr<T> {
+        self.guard.0.alloc(value)
+    }
+
+    pub fn get_mut(&mut self, p: Ptr<T>) -> Option<&mut T> {
+        self.guard.0.get_mut(p)
+    }
+
+    pub fn free(&mut self, p: Ptr<T>) -> bool {
+        self.guard.0.free(p)
+    }
+
+    pub fn take(&mut self, p: Ptr<T>) -> Option<T> {
+        self.guard.0.take(p)
+    }
+
+    pub fn clear(&mut self) {
+        self.guard.0.clear()
+    }
+}
+
+impl<'a, T> Deref for StaticPoolGuard<'a, T> {
+    type Target = Pool<T>;
+    fn deref(&self) -> &Pool<T> {
+        &self.guard.0
+    }
+}
+
+impl<'a, T> std::ops::Index<Ptr<T>> for StaticPoolGuard<'a, T> {
+    type Output = T;
+    fn index(&self, p: Ptr<T>) -> &T {
+        &self.guard.0[p]
+    }
+}
+
+impl<'a, T> std::ops::IndexMut<Ptr<T>> for StaticPoolGuard<'a, T> {
+    fn index_mut(&mut self, p: Ptr<T>) -> &mut T {
+        &mut self.guard.0[p]
+    }
+}
+
+/// Declares a `static` of `StaticPool`, e.g. `static_pool!(pub NODES: Pool<Node>);`.
+/// A block size can be given after the type, e.g. `static_pool!(NODES: Pool<Node>, 64);`.
+#[macro_export]
+macro_rules! static_pool {
+    ($(#[$attr:meta])* $vis:vis $name:ident: Pool<$t:ty> $(,)?) => {
+        $(#[$attr])*
+        $vis static $name: $crate::StaticPool<$t> = $crate::StaticPool::new();
+    };
+    ($(#[$attr:meta])* $vis:vis $name:ident: Pool<$t:ty>, $block_size:expr $(,)?) => {
+        $(#[$attr])*
+        $vis static $name: $crate::StaticPool<$t> = $crate::StaticPool::with_block_size($block_size);
+    };
+}
+
+#[test]
+fn test() {
+    static_pool!(NAMES: Pool<String>);
+    static_pool!(
+        /// Small blocks.
+        NUMBERS: Pool<u32>, 4
+    );
+
+    let ptrs = std::thread::scope(|s| {
+        let handles = (0..4)
+            .map(|t| s.spawn(move || NUMBERS.lock().alloc(t)))
+            .collect::<Vec<_>>();
+        handles
+            .into_iter()
+            .map(|h| h.join().unwrap())
+            .collect::<Vec<_>>()
+    });
+    let mut numbers = NUMBERS.lock();
+    assert_eq!(numbers.len(), 4);
+    assert_eq!(numbers.iter_blocks().count(), 1);
+    assert!(ptrs
+        .iter()
+        .enumerate()
+        .all(|(t, &p)| numbers[p] == t as u32));
+    numbers[ptrs[0]] = 10;
+    assert_eq!(numbers.take(ptrs[0]), Some(10));
+    drop(numbers);
+
+    let hello = NAMES.lock().alloc(String::from("hello"));
+    NAMES.lock().get_mut(hello).unwrap().push('!');
+    assert_eq!(NAMES.lock()[hello], "hello!");
+    assert!(NAMES.lock().free(hello));
+    assert!(NAMES.lock().is_empty());
+}
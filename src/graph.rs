@@ -0,0 +1,199 @@
+use crate::{Pool, Ptr};
+use alloc::vec::Vec;
+
+pub type NodePtr<N, E> = Ptr<GraphNode<N, E>>;
+pub type EdgePtr<N, E> = Ptr<GraphEdge<N, E>>;
+
+/// A node of `Graph`, which holds a weight and the edges incident to the node.
+#[derive(Debug)]
+pub struct GraphNode<N, E> {
+    weight: N,
+    outgoing: Vec<EdgePtr<N, E>>,
+    incoming: Vec<EdgePtr<N, E>>,
+}
+
+/// A directed edge of `Graph`, which holds a weight and its endpoints.
+#[derive(Debug)]
+pub struct GraphEdge<N, E> {
+    weight: E,
+    source: NodePtr<N, E>,
+    target: NodePtr<N, E>,
+}
+
+/// A directed graph whose nodes and edges are allocated in two pools.
+/// Removing a node or an edge does not invalidate the pointers to the others.
+#[derive(Debug)]
+pub struct Graph<N, E> {
+    nodes: Pool<GraphNode<N, E>>,
+    edges: Pool<GraphEdge<N, E>>,
+}
+
+impl<N, E> Graph<N, E> {
+    pub fn new() -> Self {
+        Self {
+            nodes: Pool::new(),
+            edges: Pool::new(),
+        }
+    }
+
+    pub fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn edge_count(&self) -> usize {
+        self.edges.len()
+    }
+
+    pub fn add_node(&mut self, weight: N) -> NodePtr<N, E> {
+        self.nodes.alloc(GraphNode {
+            weight,
+            outgoing: Vec::new(),
+            incoming: Vec::new(),
+        })
+    }
+
+    /// Adds an edge from `source` to `target`, or returns `None` if either is already removed.
+    pub fn add_edge(
+        &mut self,
+        source: NodePtr<N, E>,
+        target: NodePtr<N, E>,
+        weight: E,
+    ) -> Option<EdgePtr<N, E>> {
+        if self.nodes.get(source).is_none() || self.nodes.get(target).is_none() {
+            return None;
+        }
+        let e = self.edges.alloc(GraphEdge {
+            weight,
+            source,
+            target,
+        });
+        self.nodes[source].outgoing.push(e);
+        self.nodes[target].incoming.push(e);
+        Some(e)
+    }
+
+    pub fn node(&self, n: NodePtr<N, E>) -> Option<&N> {
+        self.nodes.get(n).map(|node| &node.get().weight)
+    }
+
+    pub fn node_mut(&mut self, n: NodePtr<N, E>) -> Option<&mut N> {
+        self.nodes.get_mut(n).map(|node| &mut node.weight)
+    }
+
+    pub fn edge(&self, e: EdgePtr<N, E>) -> Option<&E> {
+        self.edges.get(e).map(|edge| &edge.get().weight)
+    }
+
+    pub fn edge_mut(&mut self, e: EdgePtr<N, E>) -> Option<&mut E> {
+        self.edges.get_mut(e).map(|edge| &mut edge.weight)
+    }
+
+    /// Returns the source and the target of `e`.
+    pub fn endpoints(&self, e: EdgePtr<N, E>) -> Option<(NodePtr<N, E>, NodePtr<N, E>)> {
+        let edge = self.edges.get(e)?;
+        Some((edge.source, edge.target))
+    }
+
+    /// Removes `e` and returns its weight. This takes time proportional to the degrees of the endpoints.
+    pub fn remove_edge(&mut self, e: EdgePtr<N, E>) -> Option<E> {
+        let edge = self.edges.take(e)?;
+        let unlink = |edges: &mut Vec<EdgePtr<N, E>>| {
+            let i = edges.iter().position(|&f| f == e).unwrap();
+            edges.swap_remove(i);
+        };
+        unlink(&mut self.nodes[edge.source].outgoing);
+        unlink(&mut self.nodes[edge.target].incoming);
+        Some(edge.weight)
+    }
+
+    /// Removes `n` and the edges incident to it, and returns its weight.
+    pub fn remove_node(&mut self, n: NodePtr<N, E>) -> Option<N> {
+        let node = self.nodes.get(n)?;
+        let edges = node
+            .outgoing
+            .iter()
+            .chain(&node.incoming)
+            .copied()
+            .collect::<Vec<_>>();
+        for e in edges {
+            // a self-loop appears twice
+            self.remove_edge(e);
+        }
+        self.nodes.take(n).map(|node| node.weight)
+    }
+
+    /// Iterates over the edges from `n` and their targets.
+    pub fn outgoing(
+        &self,
+        n: NodePtr<N, E>,
+    ) -> impl Iterator<Item = (EdgePtr<N, E>, NodePtr<N, E>)> + '_ {
+        let edges = self
+            .nodes
+            .get(n)
+            .map_or(&[][..], |node| &node.get().outgoing);
+        edges.iter().map(move |&e| (e, self.edges[e].target))
+    }
+
+    /// Iterates over the edges to `n` and their sources.
+    pub fn incoming(
+        &self,
+        n: NodePtr<N, E>,
+    ) -> impl Iterator<Item = (EdgePtr<N, E>, NodePtr<N, E>)> + '_ {
+        let edges = self
+            .nodes
+            .get(n)
+            .map_or(&[][..], |node| &node.get().incoming);
+        edges.iter().map(move |&e| (e, self.edges[e].source))
+    }
+
+    /// Iterates over the targets of the edges from `n`.
+    pub fn neighbors(&self, n: NodePtr<N, E>) -> impl Iterator<Item = NodePtr<N, E>> + '_ {
+        self.outgoing(n).map(|(_, m)| m)
+    }
+
+    pub fn nodes(&self) -> impl Iterator<Item = (NodePtr<N, E>, &N)> {
+        self.nodes.iter().map(|(n, node)| (n, &node.weight))
+    }
+
+    pub fn edges(&self) -> impl Iterator<Item = (EdgePtr<N, E>, &E)> {
+        self.edges.iter().map(|(e, edge)| (e, &edge.weight))
+    }
+}
+
+impl<N, E> Default for Graph<N, E> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[test]
+fn test() {
+    let mut graph = Graph::new();
+    let a = graph.add_node("a");
+    let b = graph.add_node("b");
+    let c = graph.add_node("c");
+    let ab = graph.add_edge(a, b, 1).unwrap();
+    let bc = graph.add_edge(b, c, 2).unwrap();
+    let ca = graph.add_edge(c, a, 3).unwrap();
+    let cc = graph.add_edge(c, c, 4).unwrap();
+    assert_eq!((graph.node_count(), graph.edge_count()), (3, 4));
+    assert_eq!(graph.neighbors(a).collect::<Vec<_>>(), [b]);
+    assert_eq!(graph.incoming(a).collect::<Vec<_>>(), [(ca, c)]);
+    assert_eq!(graph.endpoints(bc), Some((b, c)));
+    *graph.edge_mut(ab).unwrap() += 10;
+    assert_eq!(graph.edge(ab), Some(&11));
+    *graph.node_mut(b).unwrap() = "B";
+    assert_eq!(graph.node(b), Some(&"B"));
+
+    assert_eq!(graph.remove_edge(bc), Some(2));
+    assert_eq!(graph.remove_edge(bc), None);
+    assert_eq!(graph.neighbors(b).count(), 0);
+    assert_eq!(graph.remove_node(c), Some("c"));
+    assert_eq!(graph.node_count(), 2);
+    assert_eq!(graph.edges().map(|(e, _)| e).collect::<Vec<_>>(), [ab]);
+    assert!(graph.edge(ca).is_none() && graph.edge(cc).is_none());
+    assert_eq!(graph.incoming(a).count(), 0);
+    assert!(graph.add_edge(a, c, 5).is_none());
+    assert_eq!(graph.node(a), Some(&"a"));
+    assert_eq!(graph.nodes().count(), 2);
+}
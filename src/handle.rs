@@ -0,0 +1,125 @@
+/// Defines a pool of values of a type together with a handle type which can be used only with that pool type,
+/// so that the handles of two pools of the same value type cannot be mixed up at compile time.
+///
+/// `define_pool! { pub struct Nodes(NodeId): Node; }` defines `Nodes`, which wraps `Pool<Node>`,
+/// and `NodeId`, which wraps `Ptr<Node>`. The attributes before `struct` are given to the pool type.
+/// Two pools of the same defined type are still distinguished at runtime as `Pool` does.
+/// The methods unused by the crate are not warned, since they are generated.
+#[macro_export]
+macro_rules! define_pool {
+    ($(#[$attr:meta])* $vis:vis struct $pool:ident($id:ident): $t:ty;) => {
+        $(#[$attr])*
+        $vis struct $pool($crate::Pool<$t>);
+
+        #[doc = concat!("A pointer to a value in `", stringify!($pool), "`.")]
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+        $vis struct $id($crate::Ptr<$t>);
+
+        #[allow(dead_code)]
+        impl $id {
+            pub fn ptr(self) -> $crate::Ptr<$t> {
+                self.0
+            }
+        }
+
+        #[allow(dead_code)]
+        impl $pool {
+            pub fn new() -> Self {
+                Self($crate::Pool::new())
+            }
+
+            pub fn with_block_size(block_size: usize) -> Self {
+                Self($crate::Pool::with_block_size(block_size))
+            }
+
+            pub fn pool(&self) -> &$crate::Pool<$t> {
+                &self.0
+            }
+
+            pub fn len(&self) -> usize {
+                self.0.len()
+            }
+
+            pub fn is_empty(&self) -> bool {
+                self.0.is_empty()
+            }
+
+            pub fn alloc(&mut self, value: $t) -> $id {
+                $id(self.0.alloc(value))
+            }
+
+            pub fn get(&self, id: $id) -> Option<&$t> {
+                self.0.get(id.0).map(|r| r.get())
+            }
+
+            pub fn get_mut(&mut self, id: $id) -> Option<&mut $t> {
+                self.0.get_mut(id.0)
+            }
+
+            pub fn free(&mut self, id: $id) -> bool {
+                self.0.free(id.0)
+            }
+
+            pub fn take(&mut self, id: $id) -> Option<$t> {
+                self.0.take(id.0)
+            }
+
+            pub fn iter(&self) -> impl Iterator<Item = ($id, &$t)> {
+                self.0.iter().map(|(p, value)| ($id(p), value))
+            }
+
+            pub fn iter_mut(&mut self) -> impl Iterator<Item = ($id, &mut $t)> {
+                self.0.iter_mut().map(|(p, value)| ($id(p), value))
+            }
+        }
+
+        impl Default for $pool {
+            fn default() -> Self {
+                Self::new()
+            }
+        }
+
+        impl ::core::ops::Index<$id> for $pool {
+            type Output = $t;
+            fn index(&self, id: $id) -> &$t {
+                &self.0[id.0]
+            }
+        }
+
+        impl ::core::ops::IndexMut<$id> for $pool {
+            fn index_mut(&mut self, id: $id) -> &mut $t {
+                &mut self.0[id.0]
+            }
+        }
+    };
+}
+
+#[test]
+fn test() {
+    define_pool! {
+        /// Names of people.
+        struct People(PersonId): &'static str;
+    }
+    define_pool! {
+        struct Cities(CityId): &'static str;
+    }
+    let mut people = People::with_block_size(2);
+    let mut cities = Cities::default();
+    let alice = people.alloc("Alice");
+    let bob = people.alloc("Bob");
+    let tokyo = cities.alloc("Tokyo");
+    assert_eq!((people[alice], cities[tokyo]), ("Alice", "Tokyo"));
+    assert_eq!(people.pool()[bob.ptr()], "Bob");
+    people[bob] = "Robert";
+    *people.get_mut(alice).unwrap() = "Alicia";
+    let mut names = people.iter().map(|(_, &name)| name).collect::<Vec<_>>();
+    names.sort_unstable();
+    assert_eq!(names, ["Alicia", "Robert"]);
+    people
+        .iter_mut()
+        .for_each(|(_, name)| *name = &(*name)[..3]);
+    assert_eq!(people.take(bob), Some("Rob"));
+    assert!(!people.free(bob) && people.get(bob).is_none());
+    assert!(people.free(alice) && people.is_empty());
+    assert_eq!(cities.len(), 1);
+}
@@ -0,0 +1,159 @@
+use crate::{Pool, Ptr, Ptr32, StrPool, StrPtr};
+use std::collections::hash_map::RandomState;
+use std::collections::{HashMap, HashSet};
+use std::hash::BuildHasher;
+
+/// A handle of a string interned in `Interner`, which is compared and hashed as two `u32`s.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Symbol(Ptr32<StrPtr>);
+
+impl Symbol {
+    /// Returns the bits of this symbol, e.g. to store it in a compact table.
+    pub fn to_bits(self) -> u64 {
+        self.0.to_bits()
+    }
+
+    pub fn from_bits(bits: u64) -> Self {
+        Self(Ptr32::from_bits(bits))
+    }
+}
+
+/// A set of strings deduplicated to `Symbol`s, e.g. for the identifiers of a compiler.
+///
+/// The strings are packed in the blocks of a `StrPool`, and looked up by their hashes,
+/// so each string is stored once. Unused symbols can be freed by `remove`, `retain` or `collect_garbage`,
+/// after which the symbols refer to nothing even if the same string is interned again.
+#[derive(Debug, Default)]
+pub struct Interner {
+    strings: StrPool,
+    symbols: Pool<StrPtr>,
+    /// The symbols by the hashes of their strings, several of which share a bucket only on a collision.
+    buckets: HashMap<u64, Vec<Symbol>>,
+    hasher: RandomState,
+}
+
+impl Interner {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of the symbols.
+    pub fn len(&self) -> usize {
+        self.symbols.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.symbols.is_empty()
+    }
+
+    /// Returns the symbol of `s` if it is interned.
+    pub fn get(&self, s: &str) -> Option<Symbol> {
+        let bucket = self.buckets.get(&self.hasher.hash_one(s))?;
+        bucket
+            .iter()
+            .copied()
+            .find(|&sym| self.resolve(sym) == Some(s))
+    }
+
+    /// Returns the symbol of `s`, interning it if it is not interned yet.
+    pub fn intern(&mut self, s: &str) -> Symbol {
+        if let Some(sym) = self.get(s) {
+            return sym;
+        }
+        let sym = Symbol(self.symbols.alloc32(self.strings.alloc_str(s)));
+        let hash = self.hasher.hash_one(s);
+        self.buckets.entry(hash).or_default().push(sym);
+        sym
+    }
+
+    /// Returns the string of `sym`, or `None` if it is already freed.
+    pub fn resolve(&self, sym: Symbol) -> Option<&str> {
+        let p = *self.symbols.get32(sym.0)?;
+        self.strings.get(p)
+    }
+
+    /// Frees `sym` and its string.
+    pub fn remove(&mut self, sym: Symbol) -> bool {
+        let p = match self
+            .symbols
+            .from_ptr32(sym.0)
+            .and_then(|p| self.symbols.take(p))
+        {
+            Some(p) => p,
+            None => return false,
+        };
+        let hash = self.hasher.hash_one(self.strings.get(p).unwrap());
+        let bucket = self.buckets.get_mut(&hash).unwrap();
+        bucket.retain(|&other| other != sym);
+        if bucket.is_empty() {
+            self.buckets.remove(&hash);
+        }
+        self.strings.free(p)
+    }
+
+    /// Frees the symbols for which `f` returns `false`, and returns how many symbols are freed.
+    pub fn retain(&mut self, mut f: impl FnMut(Symbol, &str) -> bool) -> usize {
+        let unused = self
+            .symbols
+            .iter()
+            .map(|(p, &s)| (self.symbol(p), s))
+            .filter(|&(sym, s)| !f(sym, self.strings.get(s).unwrap()))
+            .map(|(sym, _)| sym)
+            .collect::<Vec<_>>();
+        for &sym in &unused {
+            self.remove(sym);
+        }
+        unused.len()
+    }
+
+    /// Frees every symbol not in `live`, and returns how many symbols are freed.
+    pub fn collect_garbage(&mut self, live: impl IntoIterator<Item = Symbol>) -> usize {
+        let live = live.into_iter().collect::<HashSet<_>>();
+        self.retain(|sym, _| live.contains(&sym))
+    }
+
+    /// Iterates over the symbols and their strings.
+    pub fn iter(&self) -> impl Iterator<Item = (Symbol, &str)> {
+        self.symbols
+            .iter()
+            .map(move |(p, &s)| (self.symbol(p), self.strings.get(s).unwrap()))
+    }
+
+    fn symbol(&self, p: Ptr<StrPtr>) -> Symbol {
+        Symbol(self.symbols.to_ptr32(p).unwrap())
+    }
+}
+
+#[test]
+fn test() {
+    let mut interner = Interner::new();
+    let foo = interner.intern("foo");
+    let bar = interner.intern("bar");
+    assert_eq!(interner.intern("foo"), foo);
+    assert_ne!(foo, bar);
+    assert_eq!(interner.len(), 2);
+    assert_eq!(interner.resolve(bar), Some("bar"));
+    assert_eq!(
+        (interner.get("bar"), interner.get("baz")),
+        (Some(bar), None)
+    );
+    assert_eq!(Symbol::from_bits(foo.to_bits()), foo);
+
+    assert!(interner.remove(bar) && !interner.remove(bar));
+    assert_eq!((interner.resolve(bar), interner.get("bar")), (None, None));
+    let bar2 = interner.intern("bar");
+    assert_ne!(bar2, bar);
+
+    let names = (0..100)
+        .map(|i| interner.intern(&i.to_string()))
+        .collect::<Vec<_>>();
+    assert_eq!(interner.len(), 102);
+    assert_eq!(interner.retain(|_, s| s.len() != 1), 10);
+    assert!(interner.resolve(names[5]).is_none());
+    assert_eq!(interner.resolve(names[50]), Some("50"));
+    assert_eq!(interner.collect_garbage(names[10..20].iter().copied()), 82);
+    let mut rest = interner.iter().map(|(_, s)| s).collect::<Vec<_>>();
+    rest.sort_unstable();
+    assert_eq!(rest, (10..20).map(|i| i.to_string()).collect::<Vec<_>>());
+    assert_eq!(interner.intern("15"), names[15]);
+}
@@ -0,0 +1,422 @@
+use crate::{BlockStorage, Entry, Heap, Pool, PoolEvent, PoolId, Ptr};
+use core::marker::PhantomData;
+
+/// An iterator over the occupied entries of `Pool`, created by `Pool::iter`.
+pub struct Iter<'a, T, S: BlockStorage = Heap> {
+    blocks: core::slice::Iter<'a, crate::storage::Block<T>>,
+    entries: core::slice::Iter<'a, Entry<T>>,
+    pool_id: PoolId,
+    len: usize,
+    _storage: PhantomData<fn() -> S>,
+}
+
+impl<'a, T, S: BlockStorage> Iter<'a, T, S> {
+    pub(crate) fn new(pool: &'a Pool<T, S>) -> Self {
+        Self {
+            blocks: pool.blocks.iter(),
+            entries: [].iter(),
+            pool_id: pool.id,
+            len: pool.len,
+            _storage: PhantomData,
+        }
+    }
+}
+
+impl<'a, T, S: BlockStorage> Iterator for Iter<'a, T, S> {
+    type Item = (Ptr<T>, &'a T);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.entries.next() {
+                Some(entry @ Entry::Occupied { value, .. }) => {
+                    self.len -= 1;
+                    return Some((Ptr::to(entry, self.pool_id), value));
+                }
+                Some(Entry::Vacant { .. }) => {}
+                None => self.entries = self.blocks.next()?.iter(),
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<'a, T, S: BlockStorage> ExactSizeIterator for Iter<'a, T, S> {}
+
+impl<'a, T, S: BlockStorage> Clone for Iter<'a, T, S> {
+    fn clone(&self) -> Self {
+        Self {
+            blocks: self.blocks.clone(),
+            entries: self.entries.clone(),
+            pool_id: self.pool_id,
+            len: self.len,
+            _storage: PhantomData,
+        }
+    }
+}
+
+/// A mutable iterator over the occupied entries of `Pool`, created by `Pool::iter_mut`.
+pub struct IterMut<'a, T, S: BlockStorage = Heap> {
+    blocks: core::slice::IterMut<'a, crate::storage::Block<T>>,
+    entries: core::slice::IterMut<'a, Entry<T>>,
+    pool_id: PoolId,
+    len: usize,
+    _storage: PhantomData<fn() -> S>,
+}
+
+impl<'a, T, S: BlockStorage> IterMut<'a, T, S> {
+    pub(crate) fn new(pool: &'a mut Pool<T, S>) -> Self {
+        Self {
+            blocks: pool.blocks.iter_mut(),
+            entries: [].iter_mut(),
+            pool_id: pool.id,
+            len: pool.len,
+            _storage: PhantomData,
+        }
+    }
+}
+
+impl<'a, T, S: BlockStorage> Iterator for IterMut<'a, T, S> {
+    type Item = (Ptr<T>, &'a mut T);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.entries.next() {
+                Some(entry) => {
+                    let ptr = Ptr::to(entry, self.pool_id);
+                    if let Entry::Occupied { value, .. } = entry {
+                        self.len -= 1;
+                        return Some((ptr, value));
+                    }
+                }
+                None => self.entries = self.blocks.next()?.iter_mut(),
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<'a, T, S: BlockStorage> ExactSizeIterator for IterMut<'a, T, S> {}
+
+/// A draining iterator over the values of `Pool`, created by `Pool::drain`.
+pub struct Drain<'a, T, S: BlockStorage = Heap> {
+    pool: &'a mut Pool<T, S>,
+    block: usize,
+    slot: usize,
+    len: usize,
+}
+
+impl<'a, T, S: BlockStorage> Drain<'a, T, S> {
+    pub(crate) fn new(pool: &'a mut Pool<T, S>) -> Self {
+        let len = core::mem::replace(&mut pool.len, 0);
+        Self {
+            pool,
+            block: 0,
+            slot: 0,
+            len,
+        }
+    }
+}
+
+/// Moves out the value of the first occupied entry at or after `(block, slot)` in block order,
+/// and advances the position past it. There must be such an entry.
+fn vacate_next<T, S: BlockStorage>(
+    pool: &mut Pool<T, S>,
+    block: &mut usize,
+    slot: &mut usize,
+) -> T {
+    loop {
+        let entries = &mut pool.blocks[*block];
+        if *slot == entries.len() {
+            *block += 1;
+            *slot = 0;
+            continue;
+        }
+        let entry = &mut entries[*slot];
+        *slot += 1;
+        let ptr = Ptr::to(entry, pool.id);
+        if let Some(value) = entry.vacate(None) {
+            pool.notify(PoolEvent::Free(ptr));
+            return value;
+        }
+    }
+}
+
+impl<'a, T, S: BlockStorage> Iterator for Drain<'a, T, S> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        if self.len == 0 {
+            return None;
+        }
+        self.len -= 1;
+        Some(vacate_next(self.pool, &mut self.block, &mut self.slot))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<'a, T, S: BlockStorage> ExactSizeIterator for Drain<'a, T, S> {}
+
+impl<'a, T, S: BlockStorage> Drop for Drain<'a, T, S> {
+    fn drop(&mut self) {
+        self.for_each(drop);
+        self.pool.rebuild_free_list();
+    }
+}
+
+/// An owning iterator over the values of `Pool` in block order, created by `into_iter`.
+pub struct IntoIter<T, S: BlockStorage = Heap> {
+    pool: Pool<T, S>,
+    block: usize,
+    slot: usize,
+}
+
+impl<T, S: BlockStorage> Iterator for IntoIter<T, S> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        if self.pool.len == 0 {
+            return None;
+        }
+        self.pool.len -= 1;
+        Some(vacate_next(&mut self.pool, &mut self.block, &mut self.slot))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.pool.len, Some(self.pool.len))
+    }
+}
+
+impl<T, S: BlockStorage> ExactSizeIterator for IntoIter<T, S> {}
+
+/// Yields the values, since no pointer is of use after the pool is consumed.
+impl<T, S: BlockStorage> IntoIterator for Pool<T, S> {
+    type Item = T;
+    type IntoIter = IntoIter<T, S>;
+    fn into_iter(self) -> IntoIter<T, S> {
+        IntoIter {
+            pool: self,
+            block: 0,
+            slot: 0,
+        }
+    }
+}
+
+impl<'a, T, S: BlockStorage> IntoIterator for &'a Pool<T, S> {
+    type Item = (Ptr<T>, &'a T);
+    type IntoIter = Iter<'a, T, S>;
+    fn into_iter(self) -> Iter<'a, T, S> {
+        self.iter()
+    }
+}
+
+impl<'a, T, S: BlockStorage> IntoIterator for &'a mut Pool<T, S> {
+    type Item = (Ptr<T>, &'a mut T);
+    type IntoIter = IterMut<'a, T, S>;
+    fn into_iter(self) -> IterMut<'a, T, S> {
+        self.iter_mut()
+    }
+}
+
+impl<T> core::iter::FromIterator<T> for Pool<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut pool = Pool::new();
+        pool.extend(iter);
+        pool
+    }
+}
+
+impl<T, S: BlockStorage> Extend<T> for Pool<T, S> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        let iter = iter.into_iter();
+        self.reserve(iter.size_hint().0);
+        for value in iter {
+            self.alloc(value);
+        }
+    }
+}
+
+/// A cursor over the occupied entries of `Pool` in block order, created by `Pool::cursor_mut`,
+/// which can free the current entry or allocate new ones while walking.
+pub struct CursorMut<'a, T, S: BlockStorage = Heap> {
+    pool: &'a mut Pool<T, S>,
+    block: usize,
+    slot: usize,
+}
+
+impl<'a, T, S: BlockStorage> CursorMut<'a, T, S> {
+    pub(crate) fn new(pool: &'a mut Pool<T, S>) -> Self {
+        let mut cursor = Self {
+            pool,
+            block: 0,
+            slot: 0,
+        };
+        cursor.seek();
+        cursor
+    }
+
+    /// Moves forward to the first occupied entry at or after the current position.
+    fn seek(&mut self) {
+        while let Some(block) = self.pool.blocks.get(self.block) {
+            match block.get(self.slot) {
+                Some(entry) if entry.is_occupied() => return,
+                Some(_) => self.slot += 1,
+                None => {
+                    self.block += 1;
+                    self.slot = 0;
+                }
+            }
+        }
+    }
+
+    fn entry(&self) -> Option<&Entry<T>> {
+        self.pool.blocks.get(self.block)?.get(self.slot)
+    }
+
+    pub fn pool(&self) -> &Pool<T, S> {
+        self.pool
+    }
+
+    /// The pointer to the current entry, or `None` if the cursor has passed the last entry.
+    pub fn ptr(&self) -> Option<Ptr<T>> {
+        self.entry().map(|entry| Ptr::to(entry, self.pool.id))
+    }
+
+    pub fn current(&mut self) -> Option<&mut T> {
+        let p = self.ptr()?;
+        self.pool.get_mut(p)
+    }
+
+    pub fn move_next(&mut self) {
+        if self.entry().is_some() {
+            self.slot += 1;
+            self.seek();
+        }
+    }
+
+    /// Drops the current value and moves to the next entry. Returns `false` if there is no current entry.
+    pub fn free_current(&mut self) -> bool {
+        match self.ptr() {
+            Some(p) => {
+                self.pool.free(p);
+                self.move_next();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Moves the current value out and moves to the next entry.
+    pub fn remove_current(&mut self) -> Option<T> {
+        let value = self.pool.take(self.ptr()?);
+        self.move_next();
+        value
+    }
+
+    /// Allocates `value` without moving the cursor.
+    /// Whether the cursor visits it later depends on whether it is placed after the current entry.
+    pub fn alloc(&mut self, value: T) -> Ptr<T> {
+        self.pool.alloc(value)
+    }
+}
+
+#[test]
+fn cursor_mut() {
+    let mut pool = Pool::with_block_size(4);
+    let ptrs = (0..10).map(|i| pool.alloc(i)).collect::<Vec<_>>();
+    pool.free(ptrs[0]);
+    pool.free(ptrs[5]);
+    let mut cursor = pool.cursor_mut();
+    let mut visited = Vec::new();
+    while let Some(p) = cursor.ptr() {
+        let value = *cursor.current().unwrap();
+        visited.push(value);
+        if value % 3 == 0 {
+            assert!(cursor.free_current());
+            cursor.alloc(value * 100);
+        } else if value == 7 {
+            assert_eq!(cursor.remove_current(), Some(7));
+        } else {
+            assert_eq!(p, ptrs[value]);
+            cursor.move_next();
+        }
+    }
+    assert!(!cursor.free_current());
+    assert_eq!(cursor.remove_current(), None);
+    // a value allocated after freeing the current one takes the freed slot, which is not visited again
+    visited.sort_unstable();
+    assert_eq!(visited, [1, 2, 3, 4, 6, 7, 8, 9]);
+    let mut values = pool.iter().map(|(_, &i)| i).collect::<Vec<_>>();
+    values.sort_unstable();
+    assert_eq!(values, [1, 2, 4, 8, 300, 600, 900]);
+    pool.assert_consistent();
+}
+
+#[test]
+fn test() {
+    let mut pool = Pool::new();
+    let ptrs = (0..2000).map(|i| pool.alloc(i)).collect::<Vec<_>>();
+    for &p in ptrs.iter().step_by(2) {
+        pool.free(p);
+    }
+    assert_eq!(pool.iter().len(), 1000);
+    assert!(pool.iter().all(|(p, &i)| ptrs[i] == p && i % 2 == 1));
+    for (_, i) in pool.iter_mut() {
+        *i *= 10;
+    }
+    assert_eq!(*pool.get(ptrs[7]).unwrap(), 70);
+    let mut iter = pool.iter_mut();
+    iter.next();
+    assert_eq!(iter.len(), 999);
+    assert_eq!(Pool::<i32>::new().iter().next(), None);
+
+    let values = pool.drain().collect::<Vec<_>>();
+    assert_eq!(values.len(), 1000);
+    assert!(values.iter().all(|&i| i % 20 == 10));
+    assert!(pool.is_empty());
+    assert!(pool.get(ptrs[7]).is_none());
+    pool.assert_consistent();
+    let p = pool.alloc(1);
+    let q = pool.alloc(2);
+    assert_eq!(pool.drain().take(1).count(), 1);
+    assert!(pool.get(p).is_none() && pool.get(q).is_none());
+    pool.assert_consistent();
+}
+
+#[test]
+fn into_iter() {
+    use alloc::rc::Rc;
+
+    let mut pool = (0..10).collect::<Pool<i32>>();
+    assert_eq!(pool.len(), 10);
+    pool.extend(10..15);
+    for (_, i) in &mut pool {
+        *i *= 2;
+    }
+    assert_eq!((&pool).into_iter().map(|(_, &i)| i).sum::<i32>(), 210);
+    let p = pool.iter().find(|(_, &i)| i == 6).unwrap().0;
+    pool.free(p);
+    let mut values = pool.into_iter().collect::<Vec<_>>();
+    values.sort_unstable();
+    assert_eq!(values.len(), 14);
+    assert_eq!(values[..3], [0, 2, 4]);
+    assert_eq!(values[3], 8);
+
+    let rc = Rc::new(());
+    let pool = core::iter::repeat_with(|| rc.clone())
+        .take(5)
+        .collect::<Pool<_>>();
+    let mut iter = pool.into_iter();
+    assert_eq!(iter.len(), 5);
+    drop(iter.next());
+    drop(iter);
+    assert_eq!(Rc::strong_count(&rc), 1);
+}
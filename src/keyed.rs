@@ -0,0 +1,76 @@
+use crate::{Pool, Ptr, Ref};
+use std::collections::HashMap;
+use std::hash::Hash;
+
+/// A `Pool` which deduplicates its values by keys, e.g. for a string pool or a symbol table.
+#[derive(Debug)]
+pub struct KeyedPool<K, T> {
+    pool: Pool<T>,
+    keys: HashMap<K, Ptr<T>>,
+}
+
+impl<K: Hash + Eq, T> KeyedPool<K, T> {
+    pub fn new() -> Self {
+        Self {
+            pool: Pool::new(),
+            keys: HashMap::new(),
+        }
+    }
+
+    pub fn pool(&self) -> &Pool<T> {
+        &self.pool
+    }
+
+    /// Allocates `value` for `key` unless `key` already exists.
+    /// Returns the pointer for `key`, and whether a new value is allocated or not.
+    pub fn alloc_keyed(&mut self, key: K, value: T) -> (Ptr<T>, bool) {
+        if let Some(&ptr) = self.keys.get(&key) {
+            (ptr, false)
+        } else {
+            let ptr = self.pool.alloc(value);
+            self.keys.insert(key, ptr);
+            (ptr, true)
+        }
+    }
+
+    pub fn free_keyed(&mut self, key: &K) -> bool {
+        match self.keys.remove(key) {
+            Some(ptr) => self.pool.free(ptr),
+            None => false,
+        }
+    }
+
+    pub fn get_by_key(&self, key: &K) -> Option<Ref<'_, T>> {
+        self.keys.get(key).and_then(|&ptr| self.pool.get(ptr))
+    }
+
+    pub fn get(&self, p: Ptr<T>) -> Option<Ref<'_, T>> {
+        self.pool.get(p)
+    }
+
+    pub fn get_mut(&mut self, p: Ptr<T>) -> Option<&mut T> {
+        self.pool.get_mut(p)
+    }
+}
+
+impl<K: Hash + Eq, T> Default for KeyedPool<K, T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[test]
+fn test() {
+    let mut pool = KeyedPool::new();
+    let (a, new) = pool.alloc_keyed("a", 1);
+    assert!(new);
+    let (b, new) = pool.alloc_keyed("a", 2);
+    assert!(!new);
+    assert_eq!(a, b);
+    assert_eq!(*pool.get_by_key(&"a").unwrap(), 1);
+    assert_eq!(pool.pool().len(), 1);
+    assert!(pool.free_keyed(&"a"));
+    assert!(!pool.free_keyed(&"a"));
+    assert!(pool.get_by_key(&"a").is_none());
+    assert!(pool.get(a).is_none());
+}
@@ -0,0 +1,160 @@
+use crate::{BlockStorage, Pool, Ptr};
+use core::panic::Location;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy)]
+struct Tag {
+    generation: u32,
+    seq: u64,
+    location: &'static Location<'static>,
+}
+
+/// The creation tags of the entries of a pool, keyed by the addresses of the entries.
+/// The tag of a freed entry is left until the entry is reused, and never matches a pointer since the generation differs.
+#[derive(Debug, Default)]
+pub(crate) struct Tags {
+    next_seq: u64,
+    map: HashMap<usize, Tag>,
+}
+
+impl Tags {
+    /// Records the allocation of `ptr` at the caller.
+    #[track_caller]
+    pub(crate) fn record<T>(&mut self, ptr: Ptr<T>) {
+        let tag = Tag {
+            generation: ptr.generation,
+            seq: self.next_seq,
+            location: Location::caller(),
+        };
+        self.next_seq += 1;
+        self.map.insert(ptr.ptr.as_ptr() as usize, tag);
+    }
+
+    fn get<T>(&self, ptr: Ptr<T>) -> Option<Tag> {
+        let tag = self.map.get(&(ptr.ptr.as_ptr() as usize))?;
+        (tag.generation == ptr.generation).then_some(*tag)
+    }
+
+    /// Moves the tag of `old` to `new`, for a value moved to another entry.
+    pub(crate) fn moved<T>(&mut self, old: Ptr<T>, new: Ptr<T>) {
+        if let Some(tag) = self.get(old) {
+            let tag = Tag {
+                generation: new.generation,
+                ..tag
+            };
+            self.map.insert(new.ptr.as_ptr() as usize, tag);
+        }
+    }
+
+    /// Keeps the tags of the addresses for which `f` returns `true`.
+    pub(crate) fn retain(&mut self, mut f: impl FnMut(usize) -> bool) {
+        self.map.retain(|&addr, _| f(addr));
+    }
+}
+
+/// A value still alive in a pool, listed by `Pool::leak_report` with the context of its allocation.
+pub struct Leak<T> {
+    pub ptr: Ptr<T>,
+    /// The number of allocations of the pool before this value, or `None` if it is not recorded.
+    pub seq: Option<u64>,
+    /// Where this value is allocated, or `None` if it is not recorded.
+    pub location: Option<&'static Location<'static>>,
+}
+
+impl<T> Leak<T> {
+    pub fn type_name(&self) -> &'static str {
+        core::any::type_name::<T>()
+    }
+}
+
+impl<T, S: BlockStorage> Pool<T, S> {
+    /// Lists the values still alive in the order of their allocations, with the `debug-checks` feature.
+    /// This is meant to find the values which are never freed, e.g. before the pool is dropped.
+    pub fn leak_report(&self) -> Vec<Leak<T>> {
+        let mut leaks = self
+            .iter()
+            .map(|(ptr, _)| {
+                let tag = self.tags.get(ptr);
+                Leak {
+                    ptr,
+                    seq: tag.map(|tag| tag.seq),
+                    location: tag.map(|tag| tag.location),
+                }
+            })
+            .collect::<Vec<_>>();
+        leaks.sort_by_key(|leak| leak.seq.unwrap_or(u64::MAX));
+        leaks
+    }
+}
+
+impl<T> core::fmt::Debug for Leak<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_struct("Leak")
+            .field("ptr", &self.ptr)
+            .field("seq", &self.seq)
+            .field("location", &self.location)
+            .finish()
+    }
+}
+
+impl<T> core::fmt::Display for Leak<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(f, "{}", self.type_name())?;
+        if let Some(seq) = self.seq {
+            write!(f, " #{}", seq)?;
+        }
+        match self.location {
+            Some(location) => write!(f, " allocated at {}", location),
+            None => write!(f, " allocated at an unknown location"),
+        }
+    }
+}
+
+#[test]
+fn test() {
+    let mut pool = Pool::with_block_size(4);
+    let line = line!() + 1;
+    let ptrs = (0..10).map(|i| pool.alloc(i)).collect::<Vec<_>>();
+    let leaked = pool.alloc_cyclic(|_| 10);
+    for (i, &p) in ptrs.iter().enumerate() {
+        if i != 3 {
+            pool.free(p);
+        }
+    }
+    pool.alloc(11);
+    let p = pool.alloc(12);
+    pool.free(p);
+
+    let report = pool.leak_report();
+    assert_eq!(
+        report.iter().map(|leak| pool[leak.ptr]).collect::<Vec<_>>(),
+        [3, 10, 11]
+    );
+    assert_eq!(
+        report.iter().map(|leak| leak.seq).collect::<Vec<_>>(),
+        [Some(3), Some(10), Some(11)]
+    );
+    assert_eq!(report[0].location.unwrap().line(), line);
+    assert_eq!(report[1].ptr, leaked);
+    assert!(report[1]
+        .to_string()
+        .starts_with("i32 #10 allocated at src/leak.rs:"));
+
+    // the tags follow the values moved by compacting
+    let map = unsafe { pool.compact() };
+    let report = pool.leak_report();
+    assert!(!map.is_empty());
+    assert!(report.iter().all(|leak| leak.seq.is_some()));
+    assert_eq!(report[1].ptr, *map.get(&leaked).unwrap_or(&leaked));
+
+    // and by moving the vacant entries to the front
+    let leaked = report[1].ptr;
+    let map = pool.compact_vacant_to_front();
+    let report = pool.leak_report();
+    assert!(!map.is_empty());
+    assert_eq!(
+        report.iter().map(|leak| leak.seq).collect::<Vec<_>>(),
+        [Some(3), Some(10), Some(11)]
+    );
+    assert_eq!(report[1].ptr, *map.get(&leaked).unwrap_or(&leaked));
+}
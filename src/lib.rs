@@ -1,21 +1,78 @@
-use std::ops::Deref;
-use std::ptr::NonNull;
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+// the tests use the standard library even without the `std` feature, for the harness and its prelude
+
+extern crate alloc;
+
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+use core::convert::TryFrom;
+use core::ops::Deref;
+use core::ptr::NonNull;
+#[cfg(feature = "std")]
+use std::collections::HashMap;
 
 mod id {
-    use lazy_static::lazy_static;
-    use std::sync::atomic::{AtomicUsize, Ordering};
+    use core::sync::atomic::{AtomicUsize, Ordering};
 
-    lazy_static! {
-        static ref COUNTER: AtomicUsize = AtomicUsize::new(1);
-    }
+    static COUNTER: AtomicUsize = AtomicUsize::new(1);
 
-    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+    /// The ids are ordered by the creation of the pools.
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
     pub struct PoolId(usize);
     impl PoolId {
         pub(crate) fn gen() -> Self {
             Self(COUNTER.fetch_add(1, Ordering::Relaxed))
         }
         pub(crate) const ZERO: Self = Self(0);
+
+        pub(crate) fn to_raw(self) -> usize {
+            self.0
+        }
+        pub(crate) fn from_raw(raw: usize) -> Self {
+            Self(raw)
+        }
+
+        /// Whether the pool of this id is not dropped yet. Always `true` without the `debug-checks` feature.
+        pub fn is_alive(self) -> bool {
+            #[cfg(feature = "debug-checks")]
+            return LIVE.lock().unwrap().contains(&self.0);
+            #[cfg(not(feature = "debug-checks"))]
+            return true;
+        }
+    }
+
+    #[cfg(feature = "debug-checks")]
+    static LIVE: std::sync::Mutex<std::collections::BTreeSet<usize>> =
+        std::sync::Mutex::new(std::collections::BTreeSet::new());
+
+    /// Marks the id of a pool alive while this is alive, with the `debug-checks` feature.
+    /// Holding this as a field, instead of implementing `Drop` for the pool, keeps the drop check of the pool lenient.
+    #[derive(Debug)]
+    pub(crate) struct Liveness(#[allow(dead_code)] PoolId);
+    impl Liveness {
+        pub(crate) fn register(id: PoolId) -> Self {
+            #[cfg(feature = "debug-checks")]
+            LIVE.lock().unwrap().insert(id.0);
+            Self(id)
+        }
+    }
+    #[cfg(feature = "debug-checks")]
+    impl Drop for Liveness {
+        fn drop(&mut self) {
+            LIVE.lock().unwrap().remove(&(self.0).0);
+        }
+    }
+
+    #[cfg(feature = "debug-checks")]
+    #[test]
+    fn liveness() {
+        let id = PoolId::gen();
+        assert!(!id.is_alive());
+        let live = Liveness::register(id);
+        assert!(id.is_alive());
+        drop(live);
+        assert!(!id.is_alive());
     }
 
     #[test]
@@ -32,24 +89,280 @@ mod id {
 
 pub use id::PoolId;
 
+mod iter;
+pub use iter::{CursorMut, Drain, IntoIter, Iter, IterMut};
+
+mod builder;
+pub use builder::{FreeListPolicy, GrowthPolicy, PoolBuilder};
+
+mod storage;
+pub use storage::{Aligned, AlignedBlock, BlockStorage, Heap};
+
+mod brand;
+pub use brand::{BrandedPool, BrandedPtr};
+
+mod pinned;
+pub use pinned::PinnedPool;
+
+mod ptr32;
+pub use ptr32::Ptr32;
+
+mod list;
+pub use list::{LinkedList, ListNode};
+
+mod graph;
+pub use graph::{EdgePtr, Graph, GraphEdge, GraphNode, NodePtr};
+
+mod any;
+pub use any::{AnyPool, AnyPtr};
+
+mod dyn_pool;
+pub use dyn_pool::{Coerce, DynPool, DynPtr};
+
+mod slice;
+pub use slice::{SlicePool, SlicePtr, StrPool, StrPtr};
+
+mod multi;
+pub use multi::{BytesPtr, MultiPool, MultiPtr};
+
+mod recycle;
+pub use recycle::{RecyclingPool, Reset};
+
+#[cfg(feature = "std")]
+mod keyed;
+#[cfg(feature = "std")]
+pub use keyed::KeyedPool;
+
+#[cfg(feature = "std")]
+mod interner;
+#[cfg(feature = "std")]
+pub use interner::{Interner, Symbol};
+
+#[cfg(feature = "std")]
+mod secondary;
+#[cfg(feature = "std")]
+pub use secondary::SecondaryMap;
+
+#[cfg(feature = "std")]
+mod compact;
+#[cfg(feature = "std")]
+pub use compact::Remap;
+
+#[cfg(feature = "std")]
+mod remote;
+#[cfg(feature = "std")]
+pub use remote::RemoteFree;
+
+#[cfg(feature = "debug-checks")]
+mod leak;
+#[cfg(feature = "debug-checks")]
+pub use leak::Leak;
+
+mod checkpoint;
+pub use checkpoint::Checkpoint;
+
+#[cfg(feature = "std")]
+mod concurrent;
+#[cfg(feature = "std")]
+pub use concurrent::{ConcurrentPool, ConcurrentPtr, ConcurrentRef, EpochGuard};
+
+#[cfg(feature = "std")]
+mod global;
+#[cfg(feature = "std")]
+pub use global::{StaticPool, StaticPoolGuard};
+
+#[cfg(feature = "std")]
+mod local;
+#[cfg(feature = "std")]
+pub use local::ThreadLocalPool;
+
+#[cfg(all(feature = "mmap", unix, target_pointer_width = "64"))]
+mod mapped;
+#[cfg(all(feature = "mmap", unix, target_pointer_width = "64"))]
+pub use mapped::{Mapped, MappedBlock};
+
+mod rc;
+pub use rc::{PoolRc, RcPool};
+
+mod handle;
+
+mod soa;
+#[cfg(feature = "derive")]
+pub use mepoo_derive::PooledSoA;
+pub use soa::{PooledSoA, SoAPool};
+
+/// The items referred to by the code generated by the derive macros.
+#[doc(hidden)]
+pub mod __private {
+    pub use alloc::vec::Vec;
+    pub use core::mem::MaybeUninit;
+}
+// lets the derive macros refer to this crate by `::mepoo` in the tests
+#[cfg(test)]
+extern crate self as mepoo;
+
+#[cfg(feature = "serde")]
+mod serde_impl;
+
+#[cfg(feature = "rayon")]
+mod par;
+
+/// A slot of a block in `Pool`.
+/// A vacant entry holds the indices of the block and the slot of the next vacant entry, which forms the free list of the pool.
+/// Since the free list does not depend on the addresses of the blocks, the entries can be copied to another place as they are.
+/// The generation of an entry is incremented every time it is freed,
+/// so that a `Ptr` to a freed value never refers to another value allocated at the same place later.
 #[derive(Debug)]
-enum Entry<T> {
-    Vacant(Option<NonNull<Self>>),
-    Occupied(T),
+pub enum Entry<T> {
+    Vacant {
+        next: Option<(u32, u32)>,
+        generation: u32,
+    },
+    Occupied {
+        value: T,
+        generation: u32,
+    },
+}
+impl<T> Entry<T> {
+    pub fn is_occupied(&self) -> bool {
+        matches!(self, Entry::Occupied { .. })
+    }
+
+    pub fn generation(&self) -> u32 {
+        match self {
+            Entry::Vacant { generation, .. } | Entry::Occupied { generation, .. } => *generation,
+        }
+    }
+
+    /// Stores `value` into this vacant entry, and returns the link to the next vacant entry.
+    fn occupy(&mut self, value: T) -> Link {
+        match *self {
+            Entry::Vacant { next, generation } => {
+                *self = Entry::Occupied { value, generation };
+                next
+            }
+            Entry::Occupied { .. } => panic!("entry is already occupied"),
+        }
+    }
+
+    /// Drops the value of this entry in place if occupied, making it vacant of the next generation.
+    /// Unlike `vacate`, the value is never moved, which `PinnedPool` relies on.
+    fn vacate_in_place(&mut self, next: Link) -> bool {
+        // makes the entry vacant even if dropping the value panics
+        struct Guard<T>(*mut Entry<T>, Link, u32);
+        impl<T> Drop for Guard<T> {
+            fn drop(&mut self) {
+                let (next, generation) = (self.1, self.2);
+                unsafe { core::ptr::write(self.0, Entry::Vacant { next, generation }) };
+            }
+        }
+        let generation = self.generation().wrapping_add(1);
+        let this = self as *mut Self;
+        match unsafe { &mut *this } {
+            Entry::Occupied { value, .. } => {
+                let _guard = Guard(this, next, generation);
+                unsafe { core::ptr::drop_in_place(value) };
+                true
+            }
+            Entry::Vacant { .. } => false,
+        }
+    }
+
+    /// Moves the value out of this entry if occupied, making it vacant of the next generation.
+    fn vacate(&mut self, next: Link) -> Option<T> {
+        if !self.is_occupied() {
+            return None;
+        }
+        let generation = self.generation().wrapping_add(1);
+        match core::mem::replace(self, Entry::Vacant { next, generation }) {
+            Entry::Occupied { value, .. } => Some(value),
+            Entry::Vacant { .. } => unreachable!(),
+        }
+    }
 }
 
+/// A link of the free list, which is the indices of the block and the slot of a vacant entry, or `None` at the end.
+type Link = Option<(u32, u32)>;
+
 /// A memory pool of objects of type `T`.
 /// This is similar to typed_arena excepting that `Pool` can deallocate each object individually by `free` method.
-#[derive(Debug)]
-pub struct Pool<T> {
-    blocks: Vec<Box<[Entry<T>]>>,
-    vacant: Option<NonNull<Entry<T>>>,
+/// The blocks are allocated from `S`, which is the global heap by default.
+pub struct Pool<T, S: BlockStorage = Heap> {
+    blocks: Vec<storage::Block<T>>,
+    vacant: Link,
     id: PoolId,
+    _live: id::Liveness,
+    len: usize,
+    block_size: usize,
+    growth: GrowthPolicy,
+    free_list: FreeListPolicy,
+    max_capacity: Option<usize>,
+    // type-erased not to mention `T`, which would forbid `T` from borrowing the pool itself.
+    observer: Option<Box<dyn Fn(PoolEvent<()>)>>,
+    /// The pointers scheduled to be freed by `RemoteFree`, type-erased for the same reason as `observer`.
+    #[cfg(feature = "std")]
+    remote: Option<remote::Queue>,
+    /// The pointers allocated since the outermost `Checkpoint`, or `None` if no checkpoint is taken.
+    log: Option<Vec<Ptr<T>>>,
+    #[cfg(feature = "debug-checks")]
+    tags: leak::Tags,
+    storage: S,
+    /// The position of the first entry of each block, counted over all blocks in block order.
+    starts: Vec<usize>,
+    /// The address of the first entry and the index of each block, sorted by the address.
+    addrs: Vec<(usize, usize)>,
+}
+
+/// An event notified to the observer of `Pool`.
+pub enum PoolEvent<T> {
+    Alloc(Ptr<T>),
+    Free(Ptr<T>),
+    /// A new block is allocated. This holds the index of the block.
+    BlockAlloc(usize),
+}
+impl<T> PoolEvent<T> {
+    fn cast<U>(self) -> PoolEvent<U> {
+        match self {
+            PoolEvent::Alloc(ptr) => PoolEvent::Alloc(ptr.cast()),
+            PoolEvent::Free(ptr) => PoolEvent::Free(ptr.cast()),
+            PoolEvent::BlockAlloc(index) => PoolEvent::BlockAlloc(index),
+        }
+    }
+}
+
+/// An observer of `Pool`, whose methods are called on every allocation, deallocation and block allocation.
+/// Every closure taking `PoolEvent<T>` is an observer receiving all the events.
+pub trait PoolObserver<T> {
+    fn on_alloc(&self, _ptr: Ptr<T>) {}
+    fn on_free(&self, _ptr: Ptr<T>) {}
+    /// Called when a new block is allocated, with the index of the block.
+    fn on_block_alloc(&self, _index: usize) {}
+}
+
+impl<T, F: Fn(PoolEvent<T>)> PoolObserver<T> for F {
+    fn on_alloc(&self, ptr: Ptr<T>) {
+        self(PoolEvent::Alloc(ptr))
+    }
+    fn on_free(&self, ptr: Ptr<T>) {
+        self(PoolEvent::Free(ptr))
+    }
+    fn on_block_alloc(&self, index: usize) {
+        self(PoolEvent::BlockAlloc(index))
+    }
 }
 
+/// A pointer to a value in `Pool`, which is checked with the generation of the entry when dereferenced.
+/// `Option<Ptr<T>>` is guaranteed to be of the same size as `Ptr<T>`, since the address is never null,
+/// so `None` is the null pointer at no cost, e.g. for a link of a node which may be absent.
+///
+/// `Ptr`s are compared, ordered and hashed by all of the address, the pool id and the generation,
+/// so two pointers are equal only if they are ordered as equal.
+/// The ordering is by the address first, which differs between runs;
+/// `Pool::stable_key` gives a key ordered independently of it.
 pub struct Ptr<T> {
     ptr: NonNull<Entry<T>>,
     pool_id: PoolId,
+    generation: u32,
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -65,28 +378,69 @@ impl<'a, T> Ref<'a, T> {
 }
 impl<'a, T> Deref for Ref<'a, T> {
     type Target = T;
+    #[allow(clippy::needless_borrow)]
     fn deref(&self) -> &T {
         &self.value
     }
 }
 impl<'a, T> From<Ref<'a, T>> for Ptr<T> {
     fn from(src: Ref<'a, T>) -> Self {
-        Ptr {
-            ptr: src.entry.into(),
-            pool_id: src.pool_id,
-        }
+        Ptr::to(src.entry, src.pool_id)
     }
 }
 
 impl<T> Ptr<T> {
+    fn to(entry: &Entry<T>, pool_id: PoolId) -> Self {
+        Ptr {
+            ptr: entry.into(),
+            pool_id,
+            generation: entry.generation(),
+        }
+    }
+
+    fn cast<U>(self) -> Ptr<U> {
+        Ptr {
+            ptr: self.ptr.cast(),
+            pool_id: self.pool_id,
+            generation: self.generation,
+        }
+    }
+
+    /// A pointer which refers to no entry, e.g. a placeholder before a real pointer is assigned.
+    /// Its pool id is zero, which no pool has, so it is treated as a pointer to a freed value:
+    /// `as_ref` and `Pool::get` return `None` for it, and `Pool::free` returns `false`.
     pub const DANGLING: Self = Self {
         ptr: NonNull::dangling(),
         pool_id: PoolId::ZERO,
+        generation: 0,
     };
+
+    /// Returns `true` if this is `Ptr::DANGLING`, or any pointer of the zero pool id.
+    pub fn is_dangling(&self) -> bool {
+        self.pool_id == PoolId::ZERO
+    }
+
+    /// With the `debug-checks` feature, panics if the pool of this pointer is already dropped.
+    fn debug_assert_pool_alive(&self) {
+        #[cfg(feature = "debug-checks")]
+        assert!(
+            self.pool_id.is_alive(),
+            "pool of the pointer is already dropped"
+        );
+    }
+
+    /// # Safety
+    /// The pool which allocated this pointer must be alive,
+    /// and the returned reference must not outlive the entry.
+    /// The entry must be accessed on the thread that owns the pool, or `T: Sync`.
     pub unsafe fn as_ref<'a>(&self) -> Option<Ref<'a, T>> {
+        if self.is_dangling() {
+            return None;
+        }
+        self.debug_assert_pool_alive();
         let entry = &*self.ptr.as_ptr();
         match entry {
-            Entry::Occupied(value) => Some(Ref {
+            Entry::Occupied { value, generation } if *generation == self.generation => Some(Ref {
                 value,
                 entry,
                 pool_id: self.pool_id,
@@ -94,202 +448,2812 @@ impl<T> Ptr<T> {
             _ => None,
         }
     }
+    /// # Safety
+    /// The pool which allocated this pointer must be alive,
+    /// and no other reference to the entry may exist while the returned one is used.
+    /// The entry must be accessed on the thread that owns the pool, or `T: Sync`.
     pub unsafe fn as_mut<'a>(&self) -> Option<&'a mut T> {
+        if self.is_dangling() {
+            return None;
+        }
+        self.debug_assert_pool_alive();
         match &mut *self.ptr.as_ptr() {
-            Entry::Occupied(value) => Some(value),
+            Entry::Occupied { value, generation } if *generation == self.generation => Some(value),
             _ => None,
         }
     }
 }
 
 impl<T> Pool<T> {
-    const BLOCK_SIZE: usize = 1024;
+    /// The number of entries in a block of a pool created by `Pool::new`.
+    pub const DEFAULT_BLOCK_SIZE: usize = 1024;
 
     pub fn new() -> Self {
+        Self::with_block_size(Self::DEFAULT_BLOCK_SIZE)
+    }
+
+    /// Creates a pool which allocates `block_size` entries per block.
+    ///
+    /// # Panics
+    /// Panics if `block_size` is zero.
+    pub fn with_block_size(block_size: usize) -> Self {
+        Self::with_block_size_in(block_size, Heap)
+    }
+
+    /// Creates a pool which can allocate at least `capacity` values without allocating a new block.
+    pub fn with_capacity(capacity: usize) -> Self {
+        let mut pool = Self::new();
+        pool.reserve(capacity);
+        pool
+    }
+
+    /// Creates a pool which notifies `observer` of every allocation, deallocation and block allocation.
+    pub fn with_observer(observer: impl PoolObserver<T> + 'static) -> Self
+    where
+        T: 'static,
+    {
+        let mut pool = Self::new();
+        pool.set_observer(observer);
+        pool
+    }
+}
+
+impl<T, S: BlockStorage> Pool<T, S> {
+    /// Creates a pool which allocates its blocks from `storage`.
+    pub fn new_in(storage: S) -> Self {
+        Self::with_block_size_in(Pool::<T>::DEFAULT_BLOCK_SIZE, storage)
+    }
+
+    /// Creates a pool which allocates blocks of `block_size` entries from `storage`.
+    ///
+    /// # Panics
+    /// Panics if `block_size` is zero.
+    pub fn with_block_size_in(block_size: usize, storage: S) -> Self {
+        assert!(block_size > 0, "block size must be positive");
+        let id = PoolId::gen();
         Self {
             blocks: Vec::new(),
             vacant: None,
-            id: PoolId::gen(),
+            id,
+            _live: id::Liveness::register(id),
+            len: 0,
+            block_size,
+            growth: GrowthPolicy::Fixed,
+            free_list: FreeListPolicy::Lifo,
+            max_capacity: None,
+            observer: None,
+            #[cfg(feature = "std")]
+            remote: None,
+            log: None,
+            #[cfg(feature = "debug-checks")]
+            tags: Default::default(),
+            storage,
+            starts: Vec::new(),
+            addrs: Vec::new(),
+        }
+    }
+
+    pub fn storage(&self) -> &S {
+        &self.storage
+    }
+
+    /// Replaces the observer of this pool, e.g. to instrument a pool made by `PoolBuilder`.
+    pub fn set_observer(&mut self, observer: impl PoolObserver<T> + 'static)
+    where
+        T: 'static,
+    {
+        self.observer = Some(Box::new(move |event: PoolEvent<()>| match event.cast() {
+            PoolEvent::Alloc(ptr) => observer.on_alloc(ptr),
+            PoolEvent::Free(ptr) => observer.on_free(ptr),
+            PoolEvent::BlockAlloc(index) => observer.on_block_alloc(index),
+        }));
+    }
+
+    /// Removes the observer of this pool.
+    pub fn clear_observer(&mut self) {
+        self.observer = None;
+    }
+
+    fn notify(&self, event: PoolEvent<T>) {
+        if let Some(observer) = &self.observer {
+            observer(event.cast());
         }
     }
 
+    /// The number of entries per block, or of the first block for `GrowthPolicy::Doubling`.
     pub fn block_size(&self) -> usize {
-        Self::BLOCK_SIZE
+        self.block_size
     }
 
     pub fn id(&self) -> PoolId {
         self.id
     }
 
-    fn new_block() -> (NonNull<Entry<T>>, Box<[Entry<T>]>) {
-        let mut block = Vec::with_capacity(Self::BLOCK_SIZE);
-        let mut vacant = None;
-        for _ in 0..Self::BLOCK_SIZE {
-            block.push(Entry::Vacant(vacant));
-            vacant = NonNull::new(block.last_mut().unwrap() as *mut _);
+    /// Number of occupied entries in this pool.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Total number of entries (both vacant and occupied) in this pool.
+    pub fn capacity(&self) -> usize {
+        self.blocks.iter().map(|block| block.len()).sum()
+    }
+
+    /// Ratio of occupied entries to all entries. This is `0.0` for a pool without blocks.
+    pub fn load_factor(&self) -> f64 {
+        match self.capacity() {
+            0 => 0.0,
+            capacity => self.len as f64 / capacity as f64,
         }
-        (vacant.unwrap(), block.into_boxed_slice())
     }
 
-    pub fn alloc(&mut self, value: T) -> Ptr<T> {
-        let mut vacant = if let Some(vacant) = self.vacant {
-            vacant
-        } else {
-            let (ptr, block) = Self::new_block();
-            self.blocks.push(block);
-            self.vacant = Some(ptr);
-            ptr
+    /// Same as `load_factor`: the ratio of occupied entries to all entries.
+    pub fn occupancy(&self) -> f64 {
+        self.load_factor()
+    }
+
+    /// Returns whether `load_factor()` is at least `threshold`, e.g. to decide whether to compact the pool.
+    pub fn occupied_ratio_exceeds(&self, threshold: f64) -> bool {
+        self.load_factor() >= threshold
+    }
+
+    /// Allocates a block to be the `index`-th one, whose entries are linked in front of `vacant`.
+    fn new_block(
+        storage: &S,
+        size: usize,
+        index: usize,
+        mut vacant: Link,
+    ) -> ((u32, u32), storage::Block<T>) {
+        let index = u32::try_from(index).expect("too many blocks");
+        u32::try_from(size).expect("block is too large");
+        let mut block = storage.alloc_block(size, || Entry::Vacant {
+            next: None,
+            generation: 0,
+        });
+        assert_eq!(block.len(), size);
+        for (slot, entry) in block.iter_mut().enumerate() {
+            if let Entry::Vacant { next, .. } = entry {
+                *next = vacant;
+            }
+            vacant = Some((index, slot as u32));
+        }
+        (vacant.unwrap(), storage::Block::new(block))
+    }
+
+    /// Allocates a new block and pushes its entries onto the free list.
+    /// Returns `None` if the maximum capacity is already reached.
+    fn try_push_block(&mut self) -> Option<(u32, u32)> {
+        let size = match self.growth {
+            GrowthPolicy::Fixed => self.block_size,
+            GrowthPolicy::Doubling => self
+                .blocks
+                .last()
+                .map_or(self.block_size, |block| 2 * block.len()),
         };
-        unsafe {
-            self.vacant = match vacant.as_ref() {
-                Entry::Vacant(ptr) => *ptr,
-                _ => panic!("error"),
-            };
-            *vacant.as_mut() = Entry::Occupied(value);
+        let size = match self.max_capacity {
+            Some(max_capacity) => size.min(max_capacity.saturating_sub(self.capacity())),
+            None => size,
+        };
+        if size == 0 {
+            return None;
         }
-        Ptr {
-            ptr: vacant,
-            pool_id: self.id,
+        let (link, block) = Self::new_block(&self.storage, size, self.blocks.len(), self.vacant);
+        self.blocks.push(block);
+        self.reindex_blocks();
+        self.vacant = Some(link);
+        if self.free_list == FreeListPolicy::LowestFirst {
+            self.rebuild_free_list();
         }
+        self.notify(PoolEvent::BlockAlloc(self.blocks.len() - 1));
+        self.vacant
     }
 
-    pub fn free(&mut self, mut h: Ptr<T>) -> bool {
-        assert!(h.pool_id == self.id());
-        unsafe {
-            match h.ptr.as_mut() {
-                Entry::Vacant(_) => false,
-                _ => {
-                    *h.ptr.as_mut() = Entry::Vacant(self.vacant);
-                    self.vacant = Some(h.ptr);
-                    true
-                }
-            }
+    /// Allocates a new block and pushes its entries onto the free list.
+    ///
+    /// # Panics
+    /// Panics if the maximum capacity is already reached.
+    fn push_block(&mut self) -> (u32, u32) {
+        match self.try_push_block() {
+            Some(link) => link,
+            None => panic!(
+                "pool {:?} exceeded its maximum capacity {}",
+                self.id,
+                self.max_capacity.unwrap()
+            ),
         }
     }
 
-    pub fn get(&self, p: Ptr<T>) -> Option<Ref<T>> {
-        assert!(p.pool_id == self.id());
-        unsafe { p.as_ref() }
+    /// Allocates blocks until the capacity of this pool becomes at least `target_capacity`.
+    /// This does nothing if the pool is already large enough.
+    pub fn grow_to_capacity(&mut self, target_capacity: usize) {
+        while self.capacity() < target_capacity {
+            self.push_block();
+        }
     }
 
-    pub unsafe fn get_unsafe(&self, p: Ptr<T>) -> Option<&mut T> {
-        assert!(p.pool_id == self.id());
-        p.as_mut()
+    /// Allocates blocks so that at least `additional` more values can be allocated without allocating a new block.
+    pub fn reserve(&mut self, additional: usize) {
+        self.grow_to_capacity(self.len + additional);
     }
 
-    pub fn get_mut(&mut self, p: Ptr<T>) -> Option<&mut T> {
-        unsafe { self.get_unsafe(p) }
+    /// Returns the pointer which the next `alloc` will return, or `None` if it will allocate a new block.
+    /// The prediction holds only until the pool is mutated.
+    pub fn free_list_head(&self) -> Option<Ptr<T>> {
+        self.vacant.map(|link| Ptr::to(self.entry(link), self.id))
     }
-}
 
-impl<T> std::default::Default for Pool<T> {
-    fn default() -> Self {
-        Self::new()
+    /// Same as `free_list_head`: the next slot to be allocated, without allocating it.
+    pub fn first_vacant_ptr(&self) -> Option<Ptr<T>> {
+        self.free_list_head()
     }
-}
 
-impl<T> std::fmt::Debug for Ptr<T> {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(
-            f,
-            "Ptr {{ ptr: {:?}, pool_id: {:?} }}",
-            self.ptr, self.pool_id
-        )
+    /// # Panics
+    /// Panics if the maximum capacity of this pool is reached. Use `try_alloc` not to panic.
+    #[cfg_attr(feature = "debug-checks", track_caller)]
+    pub fn alloc(&mut self, value: T) -> Ptr<T> {
+        if self.vacant.is_none() {
+            self.push_block();
+        }
+        self.alloc_vacant(value)
     }
-}
-impl<T> Clone for Ptr<T> {
-    fn clone(&self) -> Self {
-        Ptr {
-            ptr: self.ptr,
-            pool_id: self.pool_id,
+
+    /// Same as `alloc`, but gives `value` back instead of panicking if the maximum capacity is reached.
+    #[cfg_attr(feature = "debug-checks", track_caller)]
+    pub fn try_alloc(&mut self, value: T) -> Result<Ptr<T>, T> {
+        if self.vacant.is_none() && self.try_push_block().is_none() {
+            return Err(value);
         }
+        Ok(self.alloc_vacant(value))
     }
-}
-impl<T> PartialEq for Ptr<T> {
-    fn eq(&self, rhs: &Self) -> bool {
-        self.ptr == rhs.ptr && self.pool_id == rhs.pool_id
+
+    /// Allocates `value` at the head of the free list, which must not be empty.
+    #[cfg_attr(feature = "debug-checks", track_caller)]
+    fn alloc_vacant(&mut self, value: T) -> Ptr<T> {
+        let id = self.id;
+        let entry = self.entry_mut(self.vacant.unwrap());
+        let next = entry.occupy(value);
+        let ptr = Ptr::to(entry, id);
+        self.vacant = next;
+        self.len += 1;
+        if let Some(log) = &mut self.log {
+            log.push(ptr);
+        }
+        #[cfg(feature = "debug-checks")]
+        self.tags.record(ptr);
+        self.notify(PoolEvent::Alloc(ptr));
+        ptr
     }
-}
-impl<T> std::hash::Hash for Ptr<T> {
-    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
-        self.ptr.hash(state)
+
+    pub fn free(&mut self, h: Ptr<T>) -> bool {
+        if h.is_dangling() {
+            return false;
+        }
+        assert!(h.pool_id == self.id());
+        self.drop_entry(h)
     }
-}
-impl<T> PartialOrd for Ptr<T> {
-    fn partial_cmp(&self, rhs: &Self) -> Option<std::cmp::Ordering> {
-        self.ptr.partial_cmp(&rhs.ptr)
+
+    /// Same as `free`, but moves the value out instead of dropping it.
+    pub fn take(&mut self, h: Ptr<T>) -> Option<T> {
+        if h.is_dangling() {
+            return None;
+        }
+        assert!(h.pool_id == self.id());
+        self.take_entry(h)
     }
-}
-impl<T> Ord for Ptr<T> {
-    fn cmp(&self, rhs: &Self) -> std::cmp::Ordering {
-        self.ptr.cmp(&rhs.ptr)
+
+    /// Moves the value out of the entry of `h`, which must belong to this pool.
+    fn take_entry(&mut self, h: Ptr<T>) -> Option<T> {
+        let entry = unsafe { h.ptr.as_ref() };
+        if entry.generation() != h.generation || !entry.is_occupied() {
+            return None;
+        }
+        let link = self.link_of(h.ptr);
+        let (prev, next) = self.vacant_neighbors(link);
+        let value = self.entry_mut(link).vacate(next)?;
+        self.link_vacant(prev, link);
+        self.len -= 1;
+        self.notify(PoolEvent::Free(h));
+        Some(value)
     }
-}
-impl<T> Copy for Ptr<T> {}
-impl<T> Eq for Ptr<T> {}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    /// Drops the value of the entry of `h` in place, which must belong to this pool.
+    fn drop_entry(&mut self, h: Ptr<T>) -> bool {
+        let entry = unsafe { h.ptr.as_ref() };
+        if entry.generation() != h.generation || !entry.is_occupied() {
+            return false;
+        }
+        let link = self.link_of(h.ptr);
+        let (prev, next) = self.vacant_neighbors(link);
+        // the entry is linked and uncounted before the value is dropped, which may panic
+        self.link_vacant(prev, link);
+        self.len -= 1;
+        self.entry_mut(link).vacate_in_place(next);
+        self.notify(PoolEvent::Free(h));
+        true
+    }
 
-    #[test]
-    fn simple_insert_and_remove() {
-        let mut pool = Pool::new();
-        let ptr = pool.alloc(3.14);
-        assert_eq!(*pool.get(ptr).unwrap(), 3.14);
-        *pool.get_mut(ptr).unwrap() = 2.7;
-        assert_eq!(*pool.get(ptr).unwrap(), 2.7);
-        assert!(pool.free(ptr));
-        assert!(pool.get(ptr).is_none());
+    /// Overwrites the value of `h` with `value`, and returns the old value.
+    /// `None` is returned if `h` is already freed, in which case `value` is dropped.
+    pub fn replace(&mut self, h: Ptr<T>, value: T) -> Option<T> {
+        assert!(h.pool_id == self.id());
+        self.replace_entry(h, value)
     }
 
-    #[test]
-    fn insert_many() {
-        let mut pool = Pool::new();
-        let mut ptrs = Vec::new();
-        for i in 0..4 * pool.block_size() {
-            ptrs.push(pool.alloc(i));
+    /// Exchanges the values of `a` and `b`, and returns `false` without doing anything if either is already freed.
+    pub fn swap(&mut self, a: Ptr<T>, b: Ptr<T>) -> bool {
+        assert!(a.pool_id == self.id() && b.pool_id == self.id());
+        if a.ptr == b.ptr {
+            return a.generation == b.generation && unsafe { a.as_ref() }.is_some();
+        }
+        match unsafe { (a.as_mut(), b.as_mut()) } {
+            (Some(x), Some(y)) => {
+                core::mem::swap(x, y);
+                true
+            }
+            _ => false,
         }
-        assert_eq!(pool.blocks.len(), 4);
-        assert_eq!(10, *pool.get(ptrs[10]).unwrap());
-        assert_eq!(20, *pool.get(ptrs[20]).unwrap());
-        assert_eq!(300, *pool.get(ptrs[300]).unwrap());
-        assert!(pool.free(ptrs[30]));
-        assert!(pool.get(ptrs[30]).is_none());
-        let h = pool.alloc(1111);
-        assert_eq!(h, ptrs[30]);
-        assert_eq!(pool.blocks.len(), 4);
-        pool.alloc(2222);
-        assert_eq!(pool.blocks.len(), 5);
     }
 
-    struct Node {
-        next: Option<Ptr<Node>>,
-        prev: Option<Ptr<Node>>,
+    /// Overwrites the value of the occupied entry of `h`, which must belong to this pool.
+    fn replace_entry(&mut self, h: Ptr<T>, value: T) -> Option<T> {
+        match unsafe { h.as_mut() } {
+            Some(old) => Some(core::mem::replace(old, value)),
+            None => None,
+        }
     }
 
-    #[test]
-    fn graph() {
-        let mut pool = Pool::new();
-        let h1 = pool.alloc(Node {
-            next: None,
-            prev: None,
-        });
-        let h2 = pool.alloc(Node {
-            next: None,
-            prev: None,
-        });
-        assert_ne!(h1, h2);
-        pool.get_mut(h1).unwrap().next = Some(h2);
-        pool.get_mut(h2).unwrap().prev = Some(h1);
+    /// Overwrites the values of the occupied entries of `ptrs` by `vals` respectively, and returns the old values.
+    /// `None` is returned for a vacant entry or a pointer of another pool, whose new value is dropped.
+    ///
+    /// # Panics
+    /// Panics if the numbers of `ptrs` and `vals` differ.
+    pub fn replace_batch(
+        &mut self,
+        ptrs: &[Ptr<T>],
+        vals: impl IntoIterator<Item = T>,
+    ) -> Vec<Option<T>> {
+        let vals = vals.into_iter().collect::<Vec<_>>();
+        assert_eq!(
+            ptrs.len(),
+            vals.len(),
+            "numbers of pointers and values differ"
+        );
+        ptrs.iter()
+            .zip(vals)
+            .map(|(&ptr, value)| {
+                if ptr.pool_id == self.id {
+                    self.replace_entry(ptr, value)
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
 
-        let mut map = std::collections::HashSet::new();
-        map.insert(h1);
-        map.insert(h2);
+    /// Moves the values out of the entries of `ptrs`.
+    /// `None` is returned for a vacant entry or a pointer of another pool.
+    pub fn take_batch(&mut self, ptrs: &[Ptr<T>]) -> Vec<Option<T>> {
+        let mut values = Vec::with_capacity(ptrs.len());
+        for &ptr in ptrs {
+            values.push(if ptr.pool_id == self.id {
+                self.take_entry(ptr)
+            } else {
+                None
+            });
+        }
+        values
+    }
 
-        let mut tree = std::collections::BTreeSet::new();
-        tree.insert(h1);
-        tree.insert(h2);
+    fn entry(&self, (block, slot): (u32, u32)) -> &Entry<T> {
+        &self.blocks[block as usize][slot as usize]
+    }
+
+    /// Borrows only the entry at the link, not the whole block, since the other entries may be borrowed, e.g. by `RcPool`.
+    fn entry_mut(&mut self, (block, slot): (u32, u32)) -> &mut Entry<T> {
+        self.blocks[block as usize].entry_mut(slot as usize)
+    }
+
+    /// Returns the link to the entry of `ptr`, which must belong to this pool.
+    fn link_of(&self, ptr: NonNull<Entry<T>>) -> (u32, u32) {
+        let (block, slot) = self.locate(ptr).unwrap();
+        (block as u32, slot as u32)
+    }
+
+    /// Returns the vacant entries between which the entry at `link` is to be linked when it is freed,
+    /// where `None` for the former stands for the head of the free list.
+    fn vacant_neighbors(&self, link: (u32, u32)) -> (Link, Link) {
+        match self.free_list {
+            FreeListPolicy::Lifo => (None, self.vacant),
+            FreeListPolicy::LowestFirst => {
+                // the links are ordered as the positions in block order
+                let (mut prev, mut next) = (None, self.vacant);
+                while let Some(entry) = next.filter(|&entry| entry < link) {
+                    prev = next;
+                    next = match self.entry(entry) {
+                        Entry::Vacant { next, .. } => *next,
+                        Entry::Occupied { .. } => unreachable!(),
+                    };
+                }
+                (prev, next)
+            }
+        }
+    }
+
+    /// Links the vacant entry at `link` next to `prev`, or at the head of the free list if `prev` is `None`.
+    fn link_vacant(&mut self, prev: Link, link: (u32, u32)) {
+        match prev {
+            Some(prev) => match self.entry_mut(prev) {
+                Entry::Vacant { next, .. } => *next = Some(link),
+                Entry::Occupied { .. } => unreachable!(),
+            },
+            None => self.vacant = Some(link),
+        }
+    }
+
+    /// Links all vacant entries into the free list in block order.
+    fn rebuild_free_list(&mut self) {
+        let mut vacant = None;
+        for (i, block) in self.blocks.iter_mut().enumerate().rev() {
+            for (j, entry) in block.iter_mut().enumerate().rev() {
+                if let Entry::Vacant { next, .. } = entry {
+                    *next = vacant;
+                    vacant = Some((i as u32, j as u32));
+                }
+            }
+        }
+        self.vacant = vacant;
+    }
+
+    /// Moves all occupied entries toward the end of the pool in block order,
+    /// so that the vacant entries gather at the front and are allocated from the lowest one.
+    /// Returns the mapping from the old pointers to the new ones of the moved entries;
+    /// entries which are not moved are not contained in it.
+    #[cfg(feature = "std")]
+    pub fn compact_vacant_to_front(&mut self) -> HashMap<Ptr<T>, Ptr<T>> {
+        let slots = self
+            .blocks
+            .iter_mut()
+            .flat_map(|block| block.iter_mut())
+            .map(NonNull::from)
+            .collect::<Vec<_>>();
+        let is_occupied = |i: usize| unsafe { slots[i].as_ref() }.is_occupied();
+        let mut remap = HashMap::new();
+        let (mut front, mut back) = (0, slots.len());
+        loop {
+            while front < back && !is_occupied(front) {
+                front += 1;
+            }
+            while front < back && is_occupied(back - 1) {
+                back -= 1;
+            }
+            if front == back {
+                break;
+            }
+            back -= 1;
+            let (src, dst) = unsafe { (&mut *slots[front].as_ptr(), &mut *slots[back].as_ptr()) };
+            let old = Ptr::to(src, self.id);
+            dst.occupy(src.vacate(None).unwrap());
+            remap.insert(old, Ptr::to(dst, self.id));
+            front += 1;
+        }
+        self.rebuild_free_list();
+        if let Some(log) = &mut self.log {
+            log.remap(&remap);
+        }
+        #[cfg(feature = "debug-checks")]
+        for (&old, &new) in &remap {
+            self.tags.moved(old, new);
+        }
+        remap
+    }
+
+    /// Frees every occupied entry for which `f` returns `false`, like `Vec::retain`.
+    pub fn retain(&mut self, mut f: impl FnMut(Ptr<T>, &mut T) -> bool) {
+        // keeps the lowest-first free list ordered even if `f` or dropping a value panics
+        struct Guard<'a, T, S: BlockStorage>(&'a mut Pool<T, S>);
+        impl<'a, T, S: BlockStorage> Drop for Guard<'a, T, S> {
+            fn drop(&mut self) {
+                if self.0.free_list == FreeListPolicy::LowestFirst {
+                    self.0.rebuild_free_list();
+                }
+            }
+        }
+        let guard = Guard(self);
+        let Pool {
+            blocks,
+            vacant,
+            id,
+            len,
+            observer,
+            ..
+        } = &mut *guard.0;
+        let entries = blocks.iter_mut().enumerate().flat_map(|(i, block)| {
+            let entries = block.iter_mut().enumerate();
+            entries.map(move |(j, entry)| ((i as u32, j as u32), entry))
+        });
+        for (link, entry) in entries {
+            let ptr = Ptr::to(entry, *id);
+            if let Entry::Occupied { value, .. } = entry {
+                if !f(ptr, value) {
+                    // the entry is linked and uncounted before the value is dropped, which may panic
+                    let value = entry.vacate(*vacant);
+                    *vacant = Some(link);
+                    *len -= 1;
+                    if let Some(observer) = observer {
+                        observer(PoolEvent::Free(ptr.cast()));
+                    }
+                    drop(value);
+                }
+            }
+        }
+    }
+
+    /// Frees every occupied entry whose value satisfies `f` in a pass over the blocks,
+    /// and returns how many entries are freed.
+    pub fn free_where(&mut self, mut f: impl FnMut(&T) -> bool) -> usize {
+        let len = self.len;
+        self.retain(|_, value| !f(value));
+        len - self.len
+    }
+
+    /// Frees all occupied entries, and returns how many entries are freed.
+    /// The blocks are kept to be reused.
+    pub fn free_all(&mut self) -> usize {
+        // links the freed entries even if dropping a value panics
+        struct Guard<'a, T, S: BlockStorage>(&'a mut Pool<T, S>);
+        impl<'a, T, S: BlockStorage> Drop for Guard<'a, T, S> {
+            fn drop(&mut self) {
+                self.0.rebuild_free_list();
+            }
+        }
+        let mut count = 0;
+        let guard = Guard(self);
+        let Pool {
+            blocks,
+            id,
+            len,
+            observer,
+            ..
+        } = &mut *guard.0;
+        for entry in blocks.iter_mut().flat_map(|block| block.iter_mut()) {
+            if entry.is_occupied() {
+                let ptr = Ptr::to(entry, *id);
+                let value = entry.vacate(None);
+                *len -= 1;
+                if let Some(observer) = observer {
+                    observer(PoolEvent::Free(ptr.cast()));
+                }
+                count += 1;
+                drop(value);
+            }
+        }
+        count
+    }
+
+    /// Frees all occupied entries, keeping the blocks to be reused.
+    /// Since every freed entry advances its generation, no pointer handed out before resolves anymore.
+    pub fn clear(&mut self) {
+        self.free_all();
+    }
+
+    /// Releases the blocks whose entries are all vacant, and returns how many blocks are released.
+    ///
+    /// # Safety
+    /// The memory of the released blocks is deallocated, so no pointer to an entry in them,
+    /// which is necessarily a pointer to a freed value, may be passed to this pool afterwards.
+    ///
+    /// # Panics
+    /// Panics if the blocks of the storage are not `BlockStorage::REORDERABLE`.
+    pub unsafe fn shrink_to_fit(&mut self) -> usize {
+        assert!(S::REORDERABLE, "blocks of this storage cannot be released");
+        let blocks = self.blocks.len();
+        self.blocks
+            .retain(|block| block.iter().any(|entry| entry.is_occupied()));
+        self.blocks.shrink_to_fit();
+        self.reindex_blocks();
+        self.rebuild_free_list();
+        self.forget_released_blocks();
+        blocks - self.blocks.len()
+    }
+
+    /// Frees all entries, and then calls `f` with this pool, inside of which no new blocks can be allocated.
+    /// All entries are freed again after `f` returns, so that the pool is ready for the next cycle.
+    ///
+    /// # Panics
+    /// Panics if `f` exhausts the current capacity of this pool.
+    pub fn reuse_with_same_blocks<R>(&mut self, f: impl FnOnce(&mut Self) -> R) -> R {
+        // restores the maximum capacity and frees all entries even if `f` panics
+        struct Guard<'a, T, S: BlockStorage>(&'a mut Pool<T, S>, Option<usize>);
+        impl<'a, T, S: BlockStorage> Drop for Guard<'a, T, S> {
+            fn drop(&mut self) {
+                self.0.max_capacity = self.1;
+                self.0.free_all();
+            }
+        }
+        self.free_all();
+        let max_capacity = self.max_capacity.replace(self.capacity());
+        let guard = Guard(self, max_capacity);
+        f(guard.0)
+    }
+
+    pub fn get(&self, p: Ptr<T>) -> Option<Ref<'_, T>> {
+        assert!(p.pool_id == self.id() || p.is_dangling());
+        unsafe { p.as_ref() }
+    }
+
+    /// # Safety
+    /// No other reference to the entry may exist while the returned one is used.
+    #[allow(clippy::mut_from_ref)]
+    pub unsafe fn get_unsafe(&self, p: Ptr<T>) -> Option<&mut T> {
+        assert!(p.pool_id == self.id() || p.is_dangling());
+        p.as_mut()
+    }
+
+    pub fn get_mut(&mut self, p: Ptr<T>) -> Option<&mut T> {
+        unsafe { self.get_unsafe(p) }
+    }
+
+    /// Same as `get`, but does not check that `p` belongs to this pool. The generation is still checked.
+    ///
+    /// # Safety
+    /// `p` must be allocated by this pool.
+    pub unsafe fn get_unchecked(&self, p: Ptr<T>) -> Option<Ref<'_, T>> {
+        debug_assert!(p.pool_id == self.id());
+        p.as_ref()
+    }
+
+    /// Same as `get_mut`, but does not check that `p` belongs to this pool.
+    ///
+    /// # Safety
+    /// `p` must be allocated by this pool.
+    pub unsafe fn get_unchecked_mut(&mut self, p: Ptr<T>) -> Option<&mut T> {
+        debug_assert!(p.pool_id == self.id());
+        p.as_mut()
+    }
+
+    /// Same as `free`, but does not check that `p` belongs to this pool.
+    ///
+    /// # Safety
+    /// `p` must be allocated by this pool.
+    pub unsafe fn free_unchecked(&mut self, p: Ptr<T>) -> bool {
+        debug_assert!(p.pool_id == self.id());
+        self.drop_entry(p)
+    }
+
+    /// Returns the mutable references to the values of `ptrs` at once,
+    /// or `None` if any of them is freed or two of them point to the same entry.
+    pub fn get_disjoint_mut<const N: usize>(&mut self, ptrs: [Ptr<T>; N]) -> Option<[&mut T; N]> {
+        for (i, p) in ptrs.iter().enumerate() {
+            assert!(p.pool_id == self.id());
+            if ptrs[..i].iter().any(|q| q.ptr == p.ptr) {
+                return None;
+            }
+        }
+        let refs = ptrs.map(|p| unsafe { p.as_mut() });
+        if refs.iter().any(Option::is_none) {
+            return None;
+        }
+        Some(refs.map(Option::unwrap))
+    }
+
+    /// Same as `get_disjoint_mut` for two pointers, e.g. the endpoints of an edge.
+    pub fn get2_mut(&mut self, a: Ptr<T>, b: Ptr<T>) -> Option<(&mut T, &mut T)> {
+        let [a, b] = self.get_disjoint_mut([a, b])?;
+        Some((a, b))
+    }
+
+    fn check(&self, p: Ptr<T>) -> Result<(), PoolError> {
+        if p.pool_id != self.id() {
+            return Err(PoolError::WrongPool {
+                expected: self.id(),
+                found: p.pool_id,
+            });
+        }
+        let entry = unsafe { p.ptr.as_ref() };
+        if !entry.is_occupied() {
+            Err(PoolError::Vacant)
+        } else if entry.generation() != p.generation {
+            Err(PoolError::StaleGeneration {
+                expected: p.generation,
+                found: entry.generation(),
+            })
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Same as `get`, but returns an error instead of panicking if `p` belongs to another pool.
+    pub fn try_get(&self, p: Ptr<T>) -> Result<Ref<'_, T>, PoolError> {
+        self.check(p)?;
+        Ok(self.get(p).unwrap())
+    }
+
+    /// Same as `get_mut`, but returns an error instead of panicking if `p` belongs to another pool.
+    pub fn try_get_mut(&mut self, p: Ptr<T>) -> Result<&mut T, PoolError> {
+        self.check(p)?;
+        Ok(self.get_mut(p).unwrap())
+    }
+
+    /// Same as `free`, but returns an error instead of panicking if `p` belongs to another pool.
+    pub fn try_free(&mut self, p: Ptr<T>) -> Result<(), PoolError> {
+        self.check(p)?;
+        self.free(p);
+        Ok(())
+    }
+
+    /// Asserts that `p` belongs to this pool and refers to an occupied entry.
+    /// This compiles to nothing in release builds.
+    #[inline]
+    pub fn debug_assert_ptr_valid(&self, p: Ptr<T>) {
+        debug_assert!(
+            p.pool_id == self.id(),
+            "pointer {:?} belongs to pool {:?}, not to pool {:?}",
+            p.ptr,
+            p.pool_id,
+            self.id()
+        );
+        debug_assert!(
+            self.get(p).is_some(),
+            "pointer {:?} refers to a vacant entry of pool {:?}",
+            p.ptr,
+            self.id()
+        );
+    }
+
+    /// Returns the stable representation of `p`, which does not depend on the addresses of the blocks.
+    /// Returns `None` if `p` does not belong to this pool.
+    pub fn ptr_to_repr(&self, p: Ptr<T>) -> Option<PtrRepr> {
+        if p.pool_id != self.id() {
+            return None;
+        }
+        let (block, slot) = self.locate(p.ptr)?;
+        Some(PtrRepr {
+            block,
+            slot,
+            generation: p.generation,
+        })
+    }
+
+    /// Returns the pointer represented by `repr`, or `None` if the slot is out of this pool.
+    /// The pointer may refer to a vacant entry, or to an entry of another generation.
+    pub fn ptr_from_repr(&self, repr: PtrRepr) -> Option<Ptr<T>> {
+        let entry = self.blocks.get(repr.block)?.get(repr.slot)?;
+        Some(Ptr {
+            generation: repr.generation,
+            ..Ptr::to(entry, self.id())
+        })
+    }
+
+    /// Returns the key of `p` ordered by the pool, the block, the slot and the generation,
+    /// which does not depend on the addresses of the blocks unlike the ordering of `Ptr`.
+    /// Returns `None` if `p` does not belong to this pool.
+    ///
+    /// Sorting by this key gives the same order over runs which create the pools and allocate the values in the same order,
+    /// e.g. for reproducible output derived from a `BTreeMap` keyed by the pointers.
+    pub fn stable_key(&self, p: Ptr<T>) -> Option<StableKey> {
+        let repr = self.ptr_to_repr(p)?;
+        Some(StableKey {
+            pool_id: p.pool_id,
+            block: repr.block,
+            slot: repr.slot,
+            generation: repr.generation,
+        })
+    }
+
+    /// Returns the position of the entry of `p` counted over all blocks in block order, which is less than `capacity`.
+    /// The index of an entry is kept until blocks are released or reordered, e.g. by `shrink_to_fit` or `swap_blocks`.
+    ///
+    /// # Panics
+    /// Panics if `p` belongs to another pool.
+    pub fn index_of(&self, p: Ptr<T>) -> usize {
+        assert!(p.pool_id == self.id());
+        let (block, slot) = self.locate(p.ptr).unwrap();
+        self.starts[block] + slot
+    }
+
+    /// Returns the pointer to the value at `index`, or `None` if the entry is vacant or `index` is out of this pool.
+    pub fn ptr_from_index(&self, index: usize) -> Option<Ptr<T>> {
+        let (block, slot) = self.locate_index(index)?;
+        let entry = &self.blocks[block][slot];
+        entry.is_occupied().then(|| Ptr::to(entry, self.id))
+    }
+
+    /// Allocates the value made by `init`, which receives the pointer to the value itself.
+    /// This is useful for a value which refers to itself, such as a sentinel of a circular list.
+    #[cfg_attr(feature = "debug-checks", track_caller)]
+    pub fn alloc_cyclic(&mut self, init: impl FnOnce(Ptr<T>) -> T) -> Ptr<T> {
+        let entry = self.vacant_entry();
+        let ptr = entry.ptr();
+        entry.insert(init(ptr))
+    }
+
+    /// Reserves the slot which the next allocation will use, so that its pointer is known before the value is made.
+    /// The slot stays vacant until `VacantEntry::insert` is called.
+    ///
+    /// # Panics
+    /// Panics if the maximum capacity of this pool is reached.
+    pub fn vacant_entry(&mut self) -> VacantEntry<'_, T, S> {
+        if self.vacant.is_none() {
+            self.push_block();
+        }
+        let ptr = self.free_list_head().unwrap();
+        VacantEntry { pool: self, ptr }
+    }
+
+    /// Allocates the value made by `init` from the neighbor pointers `prev` and `next`, e.g. a node of a linked list.
+    /// Linking the neighbors to the new node is left to the caller.
+    pub fn alloc_and_link(
+        &mut self,
+        init: impl FnOnce(Option<Ptr<T>>, Option<Ptr<T>>) -> T,
+        prev: Option<Ptr<T>>,
+        next: Option<Ptr<T>>,
+    ) -> Ptr<T> {
+        self.alloc(init(prev, next))
+    }
+
+    /// Same as `alloc`, but asserts in debug builds that this pool is the one of `preferred_pool_id`.
+    pub fn alloc_with_id_hint(&mut self, preferred_pool_id: PoolId, value: T) -> Ptr<T> {
+        debug_assert!(
+            preferred_pool_id == self.id(),
+            "allocation intended for pool {:?} is made in pool {:?}",
+            preferred_pool_id,
+            self.id()
+        );
+        self.alloc(value)
+    }
+
+    /// Allocates clones of all values in `vals`, and returns the pointers in the same order.
+    pub fn extend_from_slice(&mut self, vals: &[T]) -> Vec<Ptr<T>>
+    where
+        T: Clone,
+    {
+        self.reserve(vals.len());
+        vals.iter().map(|value| self.alloc(value.clone())).collect()
+    }
+
+    /// Allocates all values of `iter` after reserving `iter.len()` entries at once.
+    /// Returns the pointers in the same order.
+    pub fn alloc_bulk_from_fn<I: ExactSizeIterator<Item = T>>(&mut self, iter: I) -> Vec<Ptr<T>> {
+        self.reserve(iter.len());
+        let mut ptrs = Vec::with_capacity(iter.len());
+        ptrs.extend(iter.map(|value| self.alloc(value)));
+        ptrs
+    }
+
+    /// Allocates all values of `iter`, and returns the pointers in the same order.
+    /// The blocks for the lower bound of `iter.size_hint()` are allocated at once in advance.
+    pub fn alloc_extend(&mut self, iter: impl IntoIterator<Item = T>) -> Vec<Ptr<T>> {
+        let iter = iter.into_iter();
+        self.reserve(iter.size_hint().0);
+        let mut ptrs = Vec::with_capacity(iter.size_hint().0);
+        ptrs.extend(iter.map(|value| self.alloc(value)));
+        ptrs
+    }
+
+    /// Allocates `n` values made by `f` from their indices, and returns the pointers in the same order.
+    pub fn alloc_many(&mut self, n: usize, f: impl FnMut(usize) -> T) -> Vec<Ptr<T>> {
+        self.alloc_extend((0..n).map(f))
+    }
+
+    /// Allocates `n` default values, and returns their pointers.
+    pub fn alloc_many_default(&mut self, n: usize) -> Vec<Ptr<T>>
+    where
+        T: Default,
+    {
+        self.reserve(n);
+        (0..n).map(|_| self.alloc(T::default())).collect()
+    }
+
+    /// Iterates over the occupied entries in block order.
+    pub fn iter(&self) -> Iter<'_, T, S> {
+        Iter::new(self)
+    }
+
+    /// Iterates mutably over the occupied entries in block order.
+    pub fn iter_mut(&mut self) -> IterMut<'_, T, S> {
+        IterMut::new(self)
+    }
+
+    /// Iterates over the pointers to the occupied entries in block order.
+    pub fn ptrs(&self) -> impl ExactSizeIterator<Item = Ptr<T>> + '_ {
+        self.iter().map(|(p, _)| p)
+    }
+
+    /// Iterates over the values in block order.
+    pub fn values(&self) -> impl ExactSizeIterator<Item = &T> {
+        self.iter().map(|(_, value)| value)
+    }
+
+    /// Iterates mutably over the values in block order.
+    pub fn values_mut(&mut self) -> impl ExactSizeIterator<Item = &mut T> {
+        self.iter_mut().map(|(_, value)| value)
+    }
+
+    /// Moves all values out of this pool in block order, leaving the pool empty.
+    /// The values not yet yielded are dropped when the iterator is dropped. The blocks are kept to be reused.
+    pub fn drain(&mut self) -> Drain<'_, T, S> {
+        Drain::new(self)
+    }
+
+    /// Returns a cursor at the first occupied entry, which can free entries while walking over them.
+    pub fn cursor_mut(&mut self) -> CursorMut<'_, T, S> {
+        CursorMut::new(self)
+    }
+
+    /// Iterates over the pointers to the occupied entries in block order.
+    pub fn iter_ptrs(&self) -> impl Iterator<Item = Ptr<T>> + '_ {
+        self.iter().map(|(ptr, _)| ptr)
+    }
+
+    /// Iterates over the occupied entries in block order, skipping the first `skip` ones.
+    pub fn iter_skip_n(&self, skip: usize) -> impl Iterator<Item = (Ptr<T>, &T)> {
+        self.iter().skip(skip)
+    }
+
+    /// Iterates over at most `n` occupied entries in block order.
+    pub fn iter_take(&self, n: usize) -> impl Iterator<Item = (Ptr<T>, &T)> {
+        self.iter().take(n)
+    }
+
+    /// Iterates over the pointers to the occupied entries in ascending order of their addresses.
+    /// Unlike `iter_ptrs`, this collects and sorts the pointers in advance.
+    pub fn iter_occupied_ptrs_in_address_order(&self) -> impl Iterator<Item = Ptr<T>> {
+        let mut ptrs = self.iter_ptrs().collect::<Vec<_>>();
+        ptrs.sort();
+        ptrs.into_iter()
+    }
+
+    /// Returns all pairs of the occupied entries of `self` and `other` for which `f` returns `true`.
+    /// This is a nested loop join taking O(m * n) time.
+    pub fn zip_two_pools<U, R: BlockStorage>(
+        &self,
+        other: &Pool<U, R>,
+        f: impl Fn(&T, &U) -> bool,
+    ) -> Vec<(Ptr<T>, Ptr<U>)> {
+        let mut pairs = Vec::new();
+        for (p, t) in self.iter() {
+            for (q, u) in other.iter() {
+                if f(t, u) {
+                    pairs.push((p, q));
+                }
+            }
+        }
+        pairs
+    }
+
+    /// Counts the occupied entries for each key given by `f`.
+    pub fn histogram_by_key<K: Ord>(&self, f: impl Fn(&T) -> K) -> BTreeMap<K, usize> {
+        let mut histogram = BTreeMap::new();
+        for (_, value) in self.iter() {
+            *histogram.entry(f(value)).or_insert(0) += 1;
+        }
+        histogram
+    }
+
+    /// Same as `histogram_by_key` but for keys which are not `Ord`.
+    #[cfg(feature = "std")]
+    pub fn histogram_by_hash_key<K: core::hash::Hash + Eq>(
+        &self,
+        f: impl Fn(&T) -> K,
+    ) -> HashMap<K, usize> {
+        let mut histogram = HashMap::new();
+        for (_, value) in self.iter() {
+            *histogram.entry(f(value)).or_insert(0) += 1;
+        }
+        histogram
+    }
+
+    /// Creates a new pool of a single block which has clones of the entries of the `block_idx`-th block at the same positions.
+    /// The block is as long as the `block_idx`-th one, and the new pool has the same block size as this pool.
+    /// Returns the mapping from the pointers of this pool to the ones of the new pool.
+    #[cfg(feature = "std")]
+    pub fn copy_block_to_new_pool(&self, block_idx: usize) -> (Pool<T>, HashMap<Ptr<T>, Ptr<T>>)
+    where
+        T: Clone,
+    {
+        let mut pool = Pool::with_block_size(self.block_size);
+        let src = &self.blocks[block_idx];
+        let (_, mut block) = Pool::new_block(&pool.storage, src.len(), 0, None);
+        let mut remap = HashMap::new();
+        for (src, dst) in src.iter().zip(block.iter_mut()) {
+            if let Entry::Occupied { value, .. } = src {
+                dst.occupy(value.clone());
+                remap.insert(Ptr::to(src, self.id), Ptr::to(dst, pool.id));
+            }
+        }
+        pool.blocks.push(block);
+        pool.len = remap.len();
+        pool.reindex_blocks();
+        pool.rebuild_free_list();
+        (pool, remap)
+    }
+
+    /// Creates a new pool which has clones of all occupied entries at the same positions, with the same configuration.
+    /// Returns the mapping from the pointers of this pool to the ones of the new pool,
+    /// by which the pointers held by the cloned values can be rewritten with `Remap`. The observer is not cloned.
+    #[cfg(feature = "std")]
+    pub fn clone_with_map(&self) -> (Self, HashMap<Ptr<T>, Ptr<T>>)
+    where
+        T: Clone,
+        S: Clone,
+    {
+        let mut pool = Self::with_block_size_in(self.block_size, self.storage.clone());
+        pool.growth = self.growth;
+        pool.free_list = self.free_list;
+        pool.max_capacity = self.max_capacity;
+        let mut remap = HashMap::with_capacity(self.len);
+        for src in &self.blocks {
+            let (_, mut block) = Self::new_block(&pool.storage, src.len(), pool.blocks.len(), None);
+            for (src, dst) in src.iter().zip(block.iter_mut()) {
+                if let Entry::Occupied { value, .. } = src {
+                    dst.occupy(value.clone());
+                    remap.insert(Ptr::to(src, self.id), Ptr::to(dst, pool.id));
+                }
+            }
+            pool.blocks.push(block);
+        }
+        pool.len = self.len;
+        pool.reindex_blocks();
+        pool.rebuild_free_list();
+        (pool, remap)
+    }
+
+    /// Consumes this pool and returns all occupied values sorted by `key`.
+    pub fn into_sorted_vec<K: Ord>(mut self, key: impl FnMut(&T) -> K) -> Vec<T> {
+        let mut values = Vec::with_capacity(self.len);
+        values.extend(
+            self.blocks
+                .iter_mut()
+                .flat_map(|block| block.iter_mut())
+                .filter_map(|entry| entry.vacate(None)),
+        );
+        values.sort_by_key(key);
+        values
+    }
+
+    /// Consumes this pool and returns the occupied values in block order,
+    /// together with the table from each slot index of the pool to the index of its value (or `None` if vacant).
+    pub fn flatten_to_indexed_vec(mut self) -> (Vec<T>, Vec<Option<usize>>) {
+        let mut values = Vec::with_capacity(self.len);
+        let mut indices = Vec::with_capacity(self.capacity());
+        for entry in self.blocks.iter_mut().flat_map(|block| block.iter_mut()) {
+            indices.push(entry.vacate(None).map(|value| {
+                values.push(value);
+                values.len() - 1
+            }));
+        }
+        (values, indices)
+    }
+
+    /// Iterates over the blocks of this pool, including both vacant and occupied entries.
+    pub fn iter_blocks(&self) -> impl Iterator<Item = &[Entry<T>]> {
+        self.blocks.iter().map(|block| &**block)
+    }
+
+    /// Same as `iter_blocks` but yields the index of each block together.
+    pub fn iter_blocks_with_index(&self) -> impl Iterator<Item = (usize, &[Entry<T>])> {
+        self.iter_blocks().enumerate()
+    }
+
+    /// Iterates over the blocks of this pool mutably, for in-place transformation of whole blocks.
+    ///
+    /// # Safety
+    /// The caller must keep the free list and the pointers consistent:
+    /// the `next` links and the `generation` of any entry must not be modified,
+    /// and an entry must not be turned from vacant into occupied or vice versa.
+    pub unsafe fn iter_blocks_mut(&mut self) -> impl Iterator<Item = &mut [Entry<T>]> {
+        self.blocks.iter_mut().map(|block| &mut **block)
+    }
+
+    /// Total bytes of heap memory held by this pool, i.e. the blocks and the vector of blocks.
+    pub fn bytes_allocated(&self) -> usize {
+        let blocks = self
+            .iter_blocks()
+            .map(core::mem::size_of_val)
+            .sum::<usize>();
+        blocks + self.blocks.capacity() * core::mem::size_of::<storage::Block<T>>()
+    }
+
+    /// Checks the internal invariants of this pool, i.e. the occupied count and the free list.
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        let occupied = self
+            .iter_blocks()
+            .flat_map(|block| block.iter())
+            .filter(|entry| entry.is_occupied())
+            .count();
+        let vacant = self.capacity() - occupied;
+        let error = |violation| ValidationError {
+            pool_id: self.id,
+            blocks: self.blocks.len(),
+            vacant,
+            occupied,
+            violation,
+        };
+        if occupied != self.len {
+            return Err(error(Violation::LenMismatch { len: self.len }));
+        }
+        let mut listed = 0;
+        let mut next = self.vacant;
+        while let Some((block, slot)) = next {
+            let entry = match self.blocks.get(block as usize) {
+                Some(block) => block.get(slot as usize),
+                None => None,
+            };
+            let entry = match entry {
+                Some(entry) => entry,
+                None => return Err(error(Violation::FreeListOutOfPool)),
+            };
+            listed += 1;
+            if listed > vacant {
+                return Err(error(Violation::FreeListCycle));
+            }
+            next = match entry {
+                Entry::Vacant { next, .. } => *next,
+                Entry::Occupied { .. } => return Err(error(Violation::FreeListToOccupied)),
+            };
+        }
+        if listed < vacant {
+            return Err(error(Violation::FreeListIncomplete { listed }));
+        }
+        Ok(())
+    }
+
+    /// Panics with the details if `validate` fails. This is intended for use in tests.
+    pub fn assert_consistent(&self) {
+        if let Err(e) = self.validate() {
+            panic!("{}", e);
+        }
+    }
+
+    /// Writes the allocation state of this pool as text, one line per block,
+    /// where `'#'` is an occupied entry and `'.'` is a vacant entry, followed by a summary line.
+    #[cfg(feature = "std")]
+    pub fn write_allocation_bitmap<W: std::io::Write>(&self, w: &mut W) -> std::io::Result<()> {
+        for block in self.iter_blocks() {
+            let line = block
+                .iter()
+                .map(|entry| if entry.is_occupied() { '#' } else { '.' })
+                .collect::<String>();
+            writeln!(w, "{}", line)?;
+        }
+        writeln!(
+            w,
+            "{} blocks, {} occupied, {} vacant",
+            self.blocks.len(),
+            self.len,
+            self.capacity() - self.len
+        )
+    }
+
+    /// Returns the ratio of occupied entries for each block.
+    pub fn report_block_utilization(&self) -> Vec<f64> {
+        self.iter_blocks()
+            .map(|block| {
+                let occupied = block.iter().filter(|entry| entry.is_occupied()).count();
+                occupied as f64 / block.len() as f64
+            })
+            .collect()
+    }
+
+    /// Returns the statistics of the memory held by this pool.
+    pub fn memory_usage(&self) -> MemoryUsage {
+        let mut free_list_len = 0;
+        let mut next = self.vacant;
+        while let Some(link) = next {
+            free_list_len += 1;
+            next = match self.entry(link) {
+                Entry::Vacant { next, .. } => *next,
+                Entry::Occupied { .. } => None,
+            };
+        }
+        MemoryUsage {
+            bytes_reserved: self.bytes_allocated(),
+            bytes_live: self.len * core::mem::size_of::<Entry<T>>(),
+            blocks: self.blocks.len(),
+            block_occupancy: self.report_block_utilization(),
+            free_list_len,
+        }
+    }
+
+    /// Returns the number of blocks which have at least one occupied entry.
+    pub fn iter_occupied_count_by_block(&self) -> usize {
+        self.iter_blocks()
+            .filter(|block| block.iter().any(|entry| entry.is_occupied()))
+            .count()
+    }
+
+    /// Swaps two blocks, which changes the iteration order of the entries.
+    /// The entries themselves never move, so all pointers remain valid. The free list keeps its order,
+    /// except for `FreeListPolicy::LowestFirst`, for which it is rebuilt in the new block order.
+    ///
+    /// # Panics
+    /// Panics if `i` or `j` is out of bounds, or the blocks of the storage are not `BlockStorage::REORDERABLE`.
+    pub fn swap_blocks(&mut self, i: usize, j: usize) {
+        assert!(S::REORDERABLE, "blocks of this storage cannot be reordered");
+        self.blocks.swap(i, j);
+        self.reindex_blocks();
+        if self.free_list == FreeListPolicy::LowestFirst {
+            self.rebuild_free_list();
+            return;
+        }
+        let (i, j) = (i as u32, j as u32);
+        let swap = |link: &mut Link| match link {
+            Some((block, _)) if *block == i => *block = j,
+            Some((block, _)) if *block == j => *block = i,
+            _ => {}
+        };
+        swap(&mut self.vacant);
+        for entry in self.blocks.iter_mut().flat_map(|block| block.iter_mut()) {
+            if let Entry::Vacant { next, .. } = entry {
+                swap(next);
+            }
+        }
+    }
+
+    /// Updates `starts` and `addrs` after the blocks are changed.
+    fn reindex_blocks(&mut self) {
+        self.starts.clear();
+        self.addrs.clear();
+        let mut start = 0;
+        for (i, block) in self.blocks.iter().enumerate() {
+            self.starts.push(start);
+            self.addrs.push((block.as_ptr() as usize, i));
+            start += block.len();
+        }
+        self.addrs.sort_unstable();
+    }
+
+    /// Discards what is recorded about the entries of the blocks which are released, after `reindex_blocks`.
+    fn forget_released_blocks(&mut self) {
+        let addrs = core::mem::take(&mut self.addrs);
+        let size = core::mem::size_of::<Entry<T>>();
+        let blocks = &self.blocks;
+        let is_kept = |addr: usize| {
+            let i = addrs.partition_point(|&(start, _)| start <= addr);
+            i.checked_sub(1)
+                .is_some_and(|i| (addr - addrs[i].0) / size < blocks[addrs[i].1].len())
+        };
+        if let Some(log) = &mut self.log {
+            log.retain(|p| is_kept(p.ptr.as_ptr() as usize));
+        }
+        #[cfg(feature = "debug-checks")]
+        self.tags.retain(is_kept);
+        self.addrs = addrs;
+    }
+
+    /// Returns the indices of the block and the slot of `ptr` in O(log n) time for n blocks.
+    fn locate(&self, ptr: NonNull<Entry<T>>) -> Option<(usize, usize)> {
+        let addr = ptr.as_ptr() as usize;
+        let i = self.addrs.partition_point(|&(start, _)| start <= addr);
+        let (start, block) = *self.addrs.get(i.checked_sub(1)?)?;
+        let slot = (addr - start) / core::mem::size_of::<Entry<T>>();
+        (slot < self.blocks[block].len()).then_some((block, slot))
+    }
+
+    /// Returns the indices of the block and the slot at `index`, counted over all blocks in block order.
+    fn locate_index(&self, index: usize) -> Option<(usize, usize)> {
+        let block = self
+            .starts
+            .partition_point(|&start| start <= index)
+            .checked_sub(1)?;
+        let slot = index - self.starts[block];
+        (slot < self.blocks[block].len()).then_some((block, slot))
+    }
+}
+
+/// A vacant slot of `Pool` reserved by `Pool::vacant_entry`.
+/// Since this borrows the pool mutably, the slot cannot be taken by another allocation.
+pub struct VacantEntry<'a, T, S: BlockStorage = Heap> {
+    pool: &'a mut Pool<T, S>,
+    ptr: Ptr<T>,
+}
+
+impl<'a, T, S: BlockStorage> VacantEntry<'a, T, S> {
+    /// The pointer which `insert` will return.
+    pub fn ptr(&self) -> Ptr<T> {
+        self.ptr
+    }
+
+    #[cfg_attr(feature = "debug-checks", track_caller)]
+    pub fn insert(self, value: T) -> Ptr<T> {
+        let ptr = self.pool.alloc_vacant(value);
+        debug_assert_eq!(ptr, self.ptr);
+        ptr
+    }
+}
+
+/// The statistics of the memory of `Pool`, returned by `Pool::memory_usage`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MemoryUsage {
+    /// Same as `Pool::bytes_allocated`.
+    pub bytes_reserved: usize,
+    /// Bytes of the occupied entries.
+    pub bytes_live: usize,
+    pub blocks: usize,
+    /// Same as `Pool::report_block_utilization`.
+    pub block_occupancy: Vec<f64>,
+    /// Number of the vacant entries in the free list.
+    pub free_list_len: usize,
+}
+
+/// An invariant of `Pool` detected to be broken by `Pool::validate`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Violation {
+    /// The number of occupied entries differs from `Pool::len()`.
+    LenMismatch { len: usize },
+    /// The free list refers to an entry outside of the blocks of the pool.
+    FreeListOutOfPool,
+    /// The free list refers to an occupied entry.
+    FreeListToOccupied,
+    /// The free list contains a cycle.
+    FreeListCycle,
+    /// Some vacant entries are not reachable from the free list.
+    FreeListIncomplete { listed: usize },
+}
+
+/// The error returned by `Pool::validate`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ValidationError {
+    pub pool_id: PoolId,
+    pub blocks: usize,
+    pub vacant: usize,
+    pub occupied: usize,
+    pub violation: Violation,
+}
+
+impl core::fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(
+            f,
+            "inconsistent pool {:?} ({} blocks, {} vacant, {} occupied): {:?}",
+            self.pool_id, self.blocks, self.vacant, self.occupied, self.violation
+        )
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ValidationError {}
+
+/// The error returned by `Pool::try_get`, `Pool::try_get_mut` and `Pool::try_free`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PoolError {
+    /// The pointer belongs to another pool.
+    WrongPool { expected: PoolId, found: PoolId },
+    /// The entry of the pointer is already freed.
+    Vacant,
+    /// The entry of the pointer is already freed and occupied by another value.
+    StaleGeneration { expected: u32, found: u32 },
+}
+
+impl core::fmt::Display for PoolError {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match self {
+            PoolError::WrongPool { expected, found } => write!(
+                f,
+                "pointer belongs to pool {:?}, not to pool {:?}",
+                found, expected
+            ),
+            PoolError::Vacant => write!(f, "pointer refers to a vacant entry"),
+            PoolError::StaleGeneration { expected, found } => write!(
+                f,
+                "pointer of generation {} refers to an entry of generation {}",
+                expected, found
+            ),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for PoolError {}
+
+impl<T, S: BlockStorage + Default> core::default::Default for Pool<T, S> {
+    fn default() -> Self {
+        Self::new_in(S::default())
+    }
+}
+
+impl<T, S: BlockStorage> Pool<T, S> {
+    fn expect_valid(&self, p: Ptr<T>) {
+        assert!(
+            p.pool_id == self.id(),
+            "pointer {:?} belongs to pool {:?}, not to pool {:?}",
+            p.ptr,
+            p.pool_id,
+            self.id()
+        );
+    }
+}
+
+/// # Panics
+/// Panics if the pointer belongs to another pool or refers to a vacant entry.
+impl<T, S: BlockStorage> core::ops::Index<Ptr<T>> for Pool<T, S> {
+    type Output = T;
+    fn index(&self, p: Ptr<T>) -> &T {
+        self.expect_valid(p);
+        match self.get(p) {
+            Some(value) => value.get(),
+            None => panic!(
+                "pointer {:?} refers to a vacant entry of pool {:?}",
+                p.ptr,
+                self.id()
+            ),
+        }
+    }
+}
+
+/// # Panics
+/// Panics if the pointer belongs to another pool or refers to a vacant entry.
+impl<T, S: BlockStorage> core::ops::IndexMut<Ptr<T>> for Pool<T, S> {
+    fn index_mut(&mut self, p: Ptr<T>) -> &mut T {
+        self.expect_valid(p);
+        let id = self.id();
+        match self.get_mut(p) {
+            Some(value) => value,
+            None => panic!(
+                "pointer {:?} refers to a vacant entry of pool {:?}",
+                p.ptr, id
+            ),
+        }
+    }
+}
+
+impl<T: core::fmt::Debug, S: BlockStorage> core::fmt::Debug for Pool<T, S> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_struct("Pool")
+            .field("blocks", &self.iter_blocks().collect::<Vec<_>>())
+            .field("vacant", &self.vacant)
+            .field("id", &self.id)
+            .field("len", &self.len)
+            .field("block_size", &self.block_size)
+            .field("growth", &self.growth)
+            .field("max_capacity", &self.max_capacity)
+            .field("observer", &self.observer.is_some())
+            .finish()
+    }
+}
+
+impl<T> core::fmt::Debug for PoolEvent<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match self {
+            PoolEvent::Alloc(ptr) => write!(f, "Alloc({:?})", ptr),
+            PoolEvent::Free(ptr) => write!(f, "Free({:?})", ptr),
+            PoolEvent::BlockAlloc(index) => write!(f, "BlockAlloc({})", index),
+        }
+    }
+}
+impl<T> Clone for PoolEvent<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+impl<T> Copy for PoolEvent<T> {}
+impl<T> PartialEq for PoolEvent<T> {
+    fn eq(&self, rhs: &Self) -> bool {
+        match (self, rhs) {
+            (PoolEvent::Alloc(a), PoolEvent::Alloc(b)) => a == b,
+            (PoolEvent::Free(a), PoolEvent::Free(b)) => a == b,
+            (PoolEvent::BlockAlloc(a), PoolEvent::BlockAlloc(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+impl<T> Eq for PoolEvent<T> {}
+
+impl<T> core::fmt::Debug for Ptr<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(
+            f,
+            "Ptr {{ ptr: {:?}, pool_id: {:?}, generation: {} }}",
+            self.ptr, self.pool_id, self.generation
+        )
+    }
+}
+#[allow(clippy::non_canonical_clone_impl)]
+impl<T> Clone for Ptr<T> {
+    fn clone(&self) -> Self {
+        Ptr {
+            ptr: self.ptr,
+            pool_id: self.pool_id,
+            generation: self.generation,
+        }
+    }
+}
+impl<T> PartialEq for Ptr<T> {
+    fn eq(&self, rhs: &Self) -> bool {
+        self.ptr == rhs.ptr && self.pool_id == rhs.pool_id && self.generation == rhs.generation
+    }
+}
+impl<T> core::hash::Hash for Ptr<T> {
+    fn hash<H: core::hash::Hasher>(&self, state: &mut H) {
+        (self.ptr, self.pool_id, self.generation).hash(state)
+    }
+}
+impl<T> PartialOrd for Ptr<T> {
+    fn partial_cmp(&self, rhs: &Self) -> Option<core::cmp::Ordering> {
+        Some(self.cmp(rhs))
+    }
+}
+impl<T> Ord for Ptr<T> {
+    fn cmp(&self, rhs: &Self) -> core::cmp::Ordering {
+        (self.ptr, self.pool_id, self.generation).cmp(&(rhs.ptr, rhs.pool_id, rhs.generation))
+    }
+}
+impl<T> Copy for Ptr<T> {}
+impl<T> Eq for Ptr<T> {}
+// `Ptr` is just a handle, whose entry is accessed only via the pool or the unsafe methods.
+unsafe impl<T: Send + Sync> Send for Ptr<T> {}
+unsafe impl<T: Send + Sync> Sync for Ptr<T> {}
+
+/// A representation of `Ptr` by the indices of the block and the slot in it, returned by `Pool::ptr_to_repr`.
+/// This stays valid across serialization of the pool, unlike the address of the entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PtrRepr {
+    pub block: usize,
+    pub slot: usize,
+    pub generation: u32,
+}
+
+/// A key of `Ptr` returned by `Pool::stable_key`, whose ordering is independent of the addresses.
+/// The fields are compared in the order of declaration.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct StableKey {
+    pub pool_id: PoolId,
+    pub block: usize,
+    pub slot: usize,
+    pub generation: u32,
+}
+
+/// A plain representation of `Ptr` with a C-compatible layout, returned by `Ptr::into_raw`,
+/// e.g. to pass a pointer through a callback of a foreign library.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RawPtr {
+    pub addr: usize,
+    pub pool_id: usize,
+    pub generation: u32,
+}
+
+impl<T> Ptr<T> {
+    pub fn into_raw(self) -> RawPtr {
+        RawPtr {
+            addr: self.ptr.as_ptr() as usize,
+            pool_id: self.pool_id.to_raw(),
+            generation: self.generation,
+        }
+    }
+
+    /// # Safety
+    /// `raw` must be returned by `into_raw` of a `Ptr<T>` of the same `T`.
+    pub unsafe fn from_raw(raw: RawPtr) -> Self {
+        Ptr {
+            ptr: NonNull::new_unchecked(raw.addr as *mut Entry<T>),
+            pool_id: PoolId::from_raw(raw.pool_id),
+            generation: raw.generation,
+        }
+    }
+}
+
+/// A pointer which does not assert that the entry is alive.
+/// This can be upgraded to `Ptr` or `Ref` only while the entry is occupied by the same generation,
+/// and upgrading it with another pool just fails instead of panicking.
+pub struct WeakPtr<T>(Ptr<T>);
+
+impl<T> Ptr<T> {
+    pub fn downgrade(self) -> WeakPtr<T> {
+        WeakPtr(self)
+    }
+}
+
+impl<T> WeakPtr<T> {
+    pub fn upgrade<S: BlockStorage>(self, pool: &Pool<T, S>) -> Option<Ptr<T>> {
+        self.upgrade_ref(pool).map(Ptr::from)
+    }
+
+    pub fn upgrade_ref<S: BlockStorage>(self, pool: &Pool<T, S>) -> Option<Ref<'_, T>> {
+        if self.0.pool_id != pool.id() {
+            return None;
+        }
+        pool.get(self.0)
+    }
+
+    pub fn is_alive<S: BlockStorage>(self, pool: &Pool<T, S>) -> bool {
+        self.upgrade_ref(pool).is_some()
+    }
+}
+
+impl<T> core::fmt::Debug for WeakPtr<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(f, "WeakPtr({:?})", self.0)
+    }
+}
+impl<T> Clone for WeakPtr<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+impl<T> Copy for WeakPtr<T> {}
+impl<T> PartialEq for WeakPtr<T> {
+    fn eq(&self, rhs: &Self) -> bool {
+        self.0 == rhs.0
+    }
+}
+impl<T> Eq for WeakPtr<T> {}
+impl<T> core::hash::Hash for WeakPtr<T> {
+    fn hash<H: core::hash::Hasher>(&self, state: &mut H) {
+        self.0.hash(state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[allow(clippy::approx_constant)]
+    fn simple_insert_and_remove() {
+        let mut pool = Pool::new();
+        let ptr = pool.alloc(3.14);
+        assert_eq!(*pool.get(ptr).unwrap(), 3.14);
+        *pool.get_mut(ptr).unwrap() = 2.7;
+        assert_eq!(*pool.get(ptr).unwrap(), 2.7);
+        assert!(pool.free(ptr));
+        assert!(pool.get(ptr).is_none());
+    }
+
+    #[test]
+    fn insert_many() {
+        let mut pool = Pool::new();
+        let mut ptrs = Vec::new();
+        for i in 0..4 * pool.block_size() {
+            ptrs.push(pool.alloc(i));
+        }
+        assert_eq!(pool.blocks.len(), 4);
+        assert_eq!(10, *pool.get(ptrs[10]).unwrap());
+        assert_eq!(20, *pool.get(ptrs[20]).unwrap());
+        assert_eq!(300, *pool.get(ptrs[300]).unwrap());
+        assert!(pool.free(ptrs[30]));
+        assert!(pool.get(ptrs[30]).is_none());
+        let h = pool.alloc(1111);
+        assert_eq!(h.ptr, ptrs[30].ptr);
+        assert_ne!(h, ptrs[30]);
+        assert_eq!(pool.blocks.len(), 4);
+        pool.alloc(2222);
+        assert_eq!(pool.blocks.len(), 5);
+    }
+
+    #[test]
+    fn blocks_mut() {
+        let mut pool = Pool::new();
+        let ptrs = (0..10).map(|i| pool.alloc(i)).collect::<Vec<_>>();
+        assert!(pool.free(ptrs[3]));
+        unsafe {
+            for block in pool.iter_blocks_mut() {
+                for entry in block.iter_mut() {
+                    if let Entry::Occupied { value, .. } = entry {
+                        *value *= 10;
+                    }
+                }
+            }
+        }
+        assert_eq!(pool.iter_blocks().count(), 1);
+        let (i, block) = pool.iter_blocks_with_index().next().unwrap();
+        assert_eq!((i, block.len()), (0, pool.block_size()));
+        assert_eq!(*pool.get(ptrs[5]).unwrap(), 50);
+        assert!(pool.get(ptrs[3]).is_none());
+    }
+
+    #[test]
+    fn ptrs_and_values() {
+        let mut pool = Pool::with_block_size(4);
+        let ptrs = (0..10).map(|i| pool.alloc(i)).collect::<Vec<_>>();
+        pool.free(ptrs[3]);
+        assert_eq!(pool.ptrs().len(), 9);
+        assert!(pool.ptrs().zip(pool.iter()).all(|(p, (q, _))| p == q));
+        pool.values_mut().for_each(|i| *i *= 2);
+        assert_eq!(pool.values().sum::<i32>(), 84);
+        assert!(pool
+            .ptrs()
+            .all(|p| pool[p] == 2 * ptrs.iter().position(|&q| q == p).unwrap() as i32));
+    }
+
+    #[test]
+    fn stable_key() {
+        let mut pool1 = Pool::with_block_size(2);
+        let mut pool2 = Pool::with_block_size(2);
+        let ptrs = (0..5)
+            .flat_map(|i| [pool2.alloc(i), pool1.alloc(i)])
+            .collect::<Vec<_>>();
+        pool1.free(ptrs[1]);
+        let p = pool1.alloc(10);
+        let key = |p: Ptr<i32>| pool1.stable_key(p).or_else(|| pool2.stable_key(p)).unwrap();
+        let mut sorted = ptrs.clone();
+        sorted.push(p);
+        sorted.sort_by_key(|&p| key(p));
+        let i = sorted.iter().position(|&q| q == ptrs[1]).unwrap();
+        assert_eq!(sorted[i + 1], p);
+        assert_eq!(
+            key(p),
+            StableKey {
+                generation: 1,
+                ..key(ptrs[1])
+            }
+        );
+        // the pools are ordered by creation, and the entries by their indices
+        assert!(sorted[..6].iter().all(|&p| p.pool_id == pool1.id()));
+        let indices = sorted[6..]
+            .iter()
+            .map(|&p| pool2.index_of(p))
+            .collect::<Vec<_>>();
+        assert!(indices.windows(2).all(|w| w[0] < w[1]));
+        assert!(pool1.stable_key(ptrs[0]).is_none());
+    }
+
+    #[test]
+    fn index_of() {
+        let mut pool = PoolBuilder::new()
+            .block_size(3)
+            .growth(GrowthPolicy::Doubling)
+            .build();
+        let ptrs = (0..20).map(|i| pool.alloc(i)).collect::<Vec<_>>();
+        let mut indices = ptrs.iter().map(|&p| pool.index_of(p)).collect::<Vec<_>>();
+        assert!(ptrs
+            .iter()
+            .zip(&indices)
+            .all(|(&p, &i)| pool.ptr_from_index(i) == Some(p)));
+        pool.free(ptrs[5]);
+        assert_eq!(pool.index_of(ptrs[5]), indices[5]);
+        assert_eq!(pool.ptr_from_index(indices[5]), None);
+        let p = pool.alloc(100);
+        assert_eq!(pool.index_of(p), indices[5]);
+        assert_eq!(pool.ptr_from_index(pool.capacity()), None);
+        indices.sort_unstable();
+        indices.dedup();
+        assert_eq!(indices.len(), 20);
+        assert!(indices.iter().all(|&i| i < pool.capacity()));
+    }
+
+    #[test]
+    fn swap_blocks() {
+        let mut pool = Pool::new();
+        let ptrs = (0..2 * pool.block_size())
+            .map(|i| pool.alloc(i))
+            .collect::<Vec<_>>();
+        assert!(pool.free(ptrs[0]));
+        assert!(pool.free(ptrs[1500]));
+        pool.swap_blocks(0, 1);
+        pool.assert_consistent();
+        assert!(matches!(
+            pool.iter_blocks().next().unwrap()[0],
+            Entry::Occupied { .. }
+        ));
+        assert_eq!(*pool.get(ptrs[1]).unwrap(), 1);
+        assert_eq!(*pool.get(ptrs[2000]).unwrap(), 2000);
+        assert_eq!(pool.alloc(4444).ptr, ptrs[1500].ptr);
+        assert_eq!(pool.alloc(5555).ptr, ptrs[0].ptr);
+    }
+
+    #[test]
+    fn grow_to_capacity() {
+        let mut pool = Pool::new();
+        let block_size = pool.block_size();
+        let p0 = pool.alloc(0);
+        pool.grow_to_capacity(2 * block_size + 1);
+        assert_eq!(pool.capacity(), 3 * block_size);
+        pool.grow_to_capacity(block_size);
+        assert_eq!(pool.capacity(), 3 * block_size);
+        let ptrs = (1..3 * block_size)
+            .map(|i| pool.alloc(i))
+            .collect::<Vec<_>>();
+        assert_eq!(pool.blocks.len(), 3);
+        assert!(ptrs.iter().all(|&p| p != p0));
+        assert_eq!(*pool.get(p0).unwrap(), 0);
+    }
+
+    #[test]
+    fn with_capacity() {
+        let mut pool = Pool::with_capacity(Pool::<i32>::DEFAULT_BLOCK_SIZE + 1);
+        assert_eq!(pool.capacity(), 2 * Pool::<i32>::DEFAULT_BLOCK_SIZE);
+        let free_list_len = pool.memory_usage().free_list_len;
+        assert_eq!(free_list_len, pool.capacity());
+        (0..free_list_len as i32).for_each(|i| {
+            pool.alloc(i);
+        });
+        assert_eq!(pool.blocks.len(), 2);
+        pool.reserve(1);
+        assert_eq!(pool.blocks.len(), 3);
+        assert_eq!(Pool::<i32>::with_capacity(0).capacity(), 0);
+    }
+
+    #[test]
+    fn block_utilization() {
+        let mut pool = Pool::new();
+        assert!(pool.report_block_utilization().is_empty());
+        let ptrs = (0..pool.block_size() + pool.block_size() / 4)
+            .map(|i| pool.alloc(i))
+            .collect::<Vec<_>>();
+        assert_eq!(pool.report_block_utilization(), vec![1.0, 0.25]);
+        for &p in &ptrs[..pool.block_size() / 2] {
+            assert!(pool.free(p));
+        }
+        assert_eq!(pool.report_block_utilization(), vec![0.5, 0.25]);
+        assert_eq!(pool.iter_occupied_count_by_block(), 2);
+        for &p in &ptrs[pool.block_size() / 2..pool.block_size()] {
+            assert!(pool.free(p));
+        }
+        assert_eq!(pool.iter_occupied_count_by_block(), 1);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "vacant")]
+    fn debug_assert_ptr_valid() {
+        let mut pool = Pool::new();
+        let ptr = pool.alloc(1);
+        pool.debug_assert_ptr_valid(ptr);
+        pool.free(ptr);
+        pool.debug_assert_ptr_valid(ptr);
+    }
+
+    #[test]
+    fn iter_ptrs() {
+        let mut pool = Pool::new();
+        let ptrs = (0..pool.block_size() + 10)
+            .map(|i| pool.alloc(i))
+            .collect::<Vec<_>>();
+        for &p in ptrs.iter().step_by(2) {
+            assert!(pool.free(p));
+        }
+        let live = pool.iter_ptrs().collect::<std::collections::HashSet<_>>();
+        let expected = ptrs.iter().skip(1).step_by(2).cloned().collect();
+        assert_eq!(live, expected);
+    }
+
+    #[test]
+    fn extend_from_slice() {
+        let mut pool = Pool::new();
+        let vals = (0..pool.block_size() + 1).collect::<Vec<_>>();
+        let ptrs = pool.extend_from_slice(&vals);
+        assert_eq!(pool.len(), vals.len());
+        assert_eq!(pool.capacity(), 2 * pool.block_size());
+        assert!(ptrs
+            .iter()
+            .zip(&vals)
+            .all(|(&p, &v)| *pool.get(p).unwrap() == v));
+        let bulk = pool.alloc_bulk_from_fn(vec![10, 20].into_iter());
+        assert_eq!(*pool.get(bulk[1]).unwrap(), 20);
+        pool.free(bulk[0]);
+        pool.free(bulk[1]);
+        let defaults = pool.alloc_many_default(3);
+        assert!(defaults.iter().all(|&p| *pool.get(p).unwrap() == 0));
+        pool.reserve(pool.block_size() - 4);
+        assert_eq!(pool.capacity(), 2 * pool.block_size());
+        pool.reserve(pool.block_size());
+        assert_eq!(pool.capacity(), 3 * pool.block_size());
+    }
+
+    #[test]
+    fn iter_ptrs_in_address_order() {
+        let mut pool = Pool::new();
+        let mut ptrs = (0..3 * pool.block_size())
+            .map(|i| pool.alloc(i))
+            .collect::<Vec<_>>();
+        pool.swap_blocks(0, 2);
+        ptrs.sort();
+        let sorted = pool
+            .iter_occupied_ptrs_in_address_order()
+            .collect::<Vec<_>>();
+        assert_eq!(sorted, ptrs);
+    }
+
+    #[test]
+    fn free_list_head() {
+        let mut pool = Pool::new();
+        assert!(pool.free_list_head().is_none());
+        let p1 = pool.alloc(1);
+        let head = pool.free_list_head().unwrap();
+        assert!(pool.get(head).is_none());
+        assert_eq!(pool.alloc(2), head);
+        pool.free(p1);
+        let head = pool.free_list_head().unwrap();
+        assert_eq!(head.ptr, p1.ptr);
+        assert_eq!(pool.first_vacant_ptr(), Some(head));
+        assert_eq!(pool.alloc(3), head);
+    }
+
+    #[test]
+    fn bytes_allocated() {
+        let mut pool = Pool::<u64>::new();
+        assert_eq!(pool.bytes_allocated(), 0);
+        pool.alloc(1);
+        let entry_size = std::mem::size_of::<Entry<u64>>();
+        assert!(pool.bytes_allocated() >= pool.block_size() * entry_size);
+        pool.reserve(pool.block_size());
+        assert!(pool.bytes_allocated() >= 2 * pool.block_size() * entry_size);
+    }
+
+    #[test]
+    fn alloc_extend() {
+        let mut pool = Pool::with_block_size(10);
+        let ptrs = pool.alloc_extend((0..25).filter(|i| i % 2 == 0));
+        assert_eq!(ptrs.len(), 13);
+        assert!(ptrs.iter().enumerate().all(|(i, &p)| pool[p] == 2 * i));
+        let ptrs = pool.alloc_many(25, |i| i * 3);
+        assert_eq!(pool.capacity(), 40);
+        assert!(ptrs.iter().enumerate().all(|(i, &p)| pool[p] == 3 * i));
+        pool.assert_consistent();
+    }
+
+    #[test]
+    fn memory_usage() {
+        let mut pool = Pool::<u64>::with_block_size(4);
+        let ptrs = (0..6).map(|i| pool.alloc(i)).collect::<Vec<_>>();
+        pool.free(ptrs[0]);
+        let usage = pool.memory_usage();
+        assert_eq!(usage.bytes_reserved, pool.bytes_allocated());
+        assert_eq!(usage.bytes_live, 5 * std::mem::size_of::<Entry<u64>>());
+        assert_eq!(usage.blocks, 2);
+        assert_eq!(usage.block_occupancy, pool.report_block_utilization());
+        assert_eq!(usage.free_list_len, 3);
+    }
+
+    #[test]
+    fn validate() {
+        let mut pool = Pool::new();
+        pool.assert_consistent();
+        let ptrs = (0..pool.block_size() + 5)
+            .map(|i| pool.alloc(i))
+            .collect::<Vec<_>>();
+        for &p in ptrs.iter().step_by(3) {
+            pool.free(p);
+        }
+        pool.assert_consistent();
+
+        pool.len += 1;
+        let e = pool.validate().unwrap_err();
+        assert_eq!(e.violation, Violation::LenMismatch { len: pool.len });
+        assert_eq!(e.occupied + e.vacant, pool.capacity());
+        pool.len -= 1;
+
+        let head = pool.vacant;
+        pool.vacant = Some((0, 1));
+        assert_eq!(
+            pool.validate().unwrap_err().violation,
+            Violation::FreeListToOccupied
+        );
+        pool.vacant = Some((2, 0));
+        assert_eq!(
+            pool.validate().unwrap_err().violation,
+            Violation::FreeListOutOfPool
+        );
+        pool.vacant = head;
+        pool.assert_consistent();
+    }
+
+    #[test]
+    #[should_panic(expected = "FreeListIncomplete")]
+    fn assert_consistent() {
+        let mut pool = Pool::new();
+        pool.alloc(1);
+        pool.vacant = None;
+        pool.assert_consistent();
+    }
+
+    #[test]
+    fn observer() {
+        use std::{cell::RefCell, rc::Rc};
+        let events = Rc::new(RefCell::new(Vec::new()));
+        let mut pool = Pool::with_observer({
+            let events = events.clone();
+            move |e| events.borrow_mut().push(e)
+        });
+        let p1 = pool.alloc(1);
+        let p2 = pool.alloc(2);
+        pool.free(p1);
+        pool.free(p1);
+        assert_eq!(
+            *events.borrow(),
+            vec![
+                PoolEvent::BlockAlloc(0),
+                PoolEvent::Alloc(p1),
+                PoolEvent::Alloc(p2),
+                PoolEvent::Free(p1),
+            ]
+        );
+    }
+
+    #[test]
+    fn pool_observer() {
+        use std::{cell::Cell, rc::Rc};
+
+        // counts of allocations, deallocations and blocks
+        struct Churn(Rc<[Cell<usize>; 3]>);
+        impl PoolObserver<i32> for Churn {
+            fn on_alloc(&self, _: Ptr<i32>) {
+                self.0[0].set(self.0[0].get() + 1);
+            }
+            fn on_free(&self, _: Ptr<i32>) {
+                self.0[1].set(self.0[1].get() + 1);
+            }
+            fn on_block_alloc(&self, index: usize) {
+                self.0[2].set(index + 1);
+            }
+        }
+
+        let counts = Rc::new(<[Cell<usize>; 3]>::default());
+        let mut pool = PoolBuilder::new().block_size(4).build();
+        pool.set_observer(Churn(counts.clone()));
+        let ptrs = (0..10).map(|i| pool.alloc(i)).collect::<Vec<_>>();
+        ptrs.iter()
+            .take(3)
+            .for_each(|&p| assert!(pool.take(p).is_some()));
+        pool.free(ptrs[0]);
+        assert_eq!(counts.iter().map(Cell::get).collect::<Vec<_>>(), [10, 3, 3]);
+        pool.clear_observer();
+        pool.free(ptrs[3]);
+        assert_eq!(counts[1].get(), 3);
+    }
+
+    #[test]
+    fn free_all() {
+        let mut pool = Pool::new();
+        let ptrs = (0..pool.block_size() + 10)
+            .map(|i| pool.alloc(i.to_string()))
+            .collect::<Vec<_>>();
+        pool.free(ptrs[0]);
+        assert_eq!(pool.free_all(), pool.block_size() + 9);
+        assert!(pool.is_empty());
+        assert!(ptrs.iter().all(|&p| pool.get(p).is_none()));
+        pool.assert_consistent();
+        assert_eq!(pool.free_all(), 0);
+        for i in 0..pool.capacity() {
+            pool.alloc(i.to_string());
+        }
+        assert_eq!(pool.blocks.len(), 2);
+    }
+
+    /// A value whose drop panics if it is 2.
+    struct PanicOnDrop(i32);
+
+    impl Drop for PanicOnDrop {
+        fn drop(&mut self) {
+            if self.0 == 2 {
+                panic!("dropping {}", self.0);
+            }
+        }
+    }
+
+    #[test]
+    fn free_all_after_panic() {
+        let mut pool = Pool::new();
+        let ptrs = (0..4)
+            .map(|i| pool.alloc(PanicOnDrop(i)))
+            .collect::<Vec<_>>();
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| pool.free_all()));
+        assert!(result.is_err());
+        pool.assert_consistent();
+        assert!(pool.get(ptrs[2]).is_none());
+        let len = pool.iter_ptrs().count();
+        assert_eq!(pool.len(), len);
+        assert_eq!(pool.free_all(), len);
+        assert!(pool.is_empty());
+    }
+
+    #[test]
+    fn reuse_with_same_blocks() {
+        let mut pool = Pool::new();
+        pool.alloc(0);
+        let sum = pool.reuse_with_same_blocks(|pool| {
+            assert!(pool.is_empty());
+            let ptrs = (0..pool.block_size())
+                .map(|i| pool.alloc(i))
+                .collect::<Vec<_>>();
+            ptrs.iter().map(|&p| *pool.get(p).unwrap()).sum::<usize>()
+        });
+        assert_eq!(sum, (0..pool.block_size()).sum::<usize>());
+        assert!(pool.is_empty());
+        assert_eq!(pool.blocks.len(), 1);
+        pool.reserve(pool.block_size() + 1);
+        assert_eq!(pool.blocks.len(), 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "maximum capacity")]
+    fn reuse_with_same_blocks_overflow() {
+        let mut pool = Pool::new();
+        pool.reserve(1);
+        pool.reuse_with_same_blocks(|pool| {
+            for i in 0..=pool.block_size() {
+                pool.alloc(i);
+            }
+        });
+    }
+
+    #[test]
+    fn reuse_with_same_blocks_after_panic() {
+        let mut pool = Pool::new();
+        pool.reserve(1);
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            pool.reuse_with_same_blocks(|pool| {
+                for i in 0..=pool.block_size() {
+                    pool.alloc(i);
+                }
+            })
+        }));
+        assert!(result.is_err());
+        assert!(pool.is_empty());
+        pool.assert_consistent();
+        for i in 0..=pool.block_size() {
+            pool.alloc(i);
+        }
+        assert_eq!(pool.blocks.len(), 2);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn write_allocation_bitmap() {
+        let mut pool = Pool::new();
+        let ptrs = (0..pool.block_size() + 2)
+            .map(|i| pool.alloc(i))
+            .collect::<Vec<_>>();
+        pool.free(ptrs[0]);
+        let mut buf = Vec::new();
+        pool.write_allocation_bitmap(&mut buf).unwrap();
+        let text = String::from_utf8(buf).unwrap();
+        let lines = text.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0], format!("{}.", "#".repeat(pool.block_size() - 1)));
+        assert_eq!(lines[1].matches('#').count(), 2);
+        assert_eq!(lines[2], "2 blocks, 1025 occupied, 1023 vacant");
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "intended for pool")]
+    fn alloc_with_id_hint() {
+        let mut pool1 = Pool::new();
+        let pool2 = Pool::<i32>::new();
+        let ptr = pool1.alloc_with_id_hint(pool1.id(), 1);
+        assert_eq!(*pool1.get(ptr).unwrap(), 1);
+        pool1.alloc_with_id_hint(pool2.id(), 2);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn compact_vacant_to_front() {
+        let mut pool = Pool::new();
+        let ptrs = (0..pool.block_size() + 100)
+            .map(|i| pool.alloc(i))
+            .collect::<Vec<_>>();
+        for &p in ptrs.iter().step_by(2) {
+            pool.free(p);
+        }
+        let remap = pool.compact_vacant_to_front();
+        pool.assert_consistent();
+        assert_eq!(pool.report_block_utilization(), vec![0.0, 562.0 / 1024.0]);
+        for (i, &p) in ptrs.iter().enumerate().skip(1).step_by(2) {
+            let p = remap.get(&p).copied().unwrap_or(p);
+            assert_eq!(*pool.get(p).unwrap(), i);
+        }
+        let first = pool.blocks[0].as_ptr();
+        assert_eq!(pool.alloc(0).ptr.as_ptr() as *const _, first);
+    }
+
+    #[test]
+    fn iter_skip_n() {
+        let mut pool = Pool::new();
+        let ptrs = (0..10).map(|i| pool.alloc(i)).collect::<Vec<_>>();
+        pool.free(ptrs[0]);
+        let all = pool.iter_ptrs().collect::<Vec<_>>();
+        let rest = pool.iter_skip_n(3).collect::<Vec<_>>();
+        assert_eq!(rest.len(), 6);
+        assert!(rest.iter().zip(&all[3..]).all(|((p, _), q)| p == q));
+        assert!(rest.iter().all(|&(p, &v)| ptrs[v] == p));
+        assert_eq!(pool.iter_skip_n(9).count(), 0);
+        let page = pool.iter_take(3).map(|(p, _)| p).collect::<Vec<_>>();
+        assert_eq!(page, all[..3]);
+        assert_eq!(pool.iter_take(100).count(), 9);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn clone_with_map() {
+        #[derive(Clone)]
+        struct Node {
+            value: i32,
+            next: Option<Ptr<Node>>,
+        }
+        let mut pool = Pool::with_block_size(3);
+        let ptrs = (0..5)
+            .map(|value| pool.alloc(Node { value, next: None }))
+            .collect::<Vec<_>>();
+        pool.free(ptrs[1]);
+        pool[ptrs[0]].next = Some(ptrs[4]);
+        let (mut copy, remap) = pool.clone_with_map();
+        copy.assert_consistent();
+        assert_eq!((copy.len(), copy.capacity(), remap.len()), (4, 6, 4));
+        for (_, node) in copy.iter_mut() {
+            node.next.remap(&remap);
+        }
+        let p0 = remap[&ptrs[0]];
+        copy[p0].value = 10;
+        assert_eq!(copy[copy[p0].next.unwrap()].value, 4);
+        assert_eq!(pool[ptrs[0]].value, 0);
+        assert_eq!(copy.ptr_to_repr(p0), pool.ptr_to_repr(ptrs[0]));
+        assert!(!remap.contains_key(&ptrs[1]));
+        copy.alloc(Node {
+            value: 5,
+            next: None,
+        });
+        assert_eq!(copy.capacity(), 6);
+    }
+
+    #[test]
+    fn into_sorted_vec() {
+        let mut pool = Pool::new();
+        let ptrs = [3, 1, 4, 1, 5, 9, 2, 6]
+            .iter()
+            .map(|&i| pool.alloc(i))
+            .collect::<Vec<_>>();
+        pool.free(ptrs[2]);
+        assert_eq!(pool.into_sorted_vec(|&i| i), vec![1, 1, 2, 3, 5, 6, 9]);
+    }
+
+    #[test]
+    fn zip_two_pools() {
+        let mut pool1 = Pool::new();
+        let mut pool2 = Pool::new();
+        let a = (0..10).map(|i| pool1.alloc(i)).collect::<Vec<_>>();
+        let b = (0..10)
+            .map(|i| pool2.alloc(i.to_string()))
+            .collect::<Vec<_>>();
+        let mut pairs = pool1.zip_two_pools(&pool2, |&i, s| (i * i).to_string() == *s);
+        pairs.sort_by_key(|&(p, _)| *pool1.get(p).unwrap());
+        assert_eq!(
+            pairs,
+            vec![(a[0], b[0]), (a[1], b[1]), (a[2], b[4]), (a[3], b[9])]
+        );
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn histogram_by_key() {
+        let mut pool = Pool::new();
+        let ptrs = (0..10).map(|i| pool.alloc(i)).collect::<Vec<_>>();
+        pool.free(ptrs[0]);
+        let histogram = pool.histogram_by_key(|&i| i % 3);
+        assert_eq!(
+            histogram.into_iter().collect::<Vec<_>>(),
+            vec![(0, 3), (1, 3), (2, 3)]
+        );
+        let histogram = pool.histogram_by_hash_key(|&i| i < 5);
+        assert_eq!((histogram[&true], histogram[&false]), (4, 5));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn copy_block_to_new_pool() {
+        let mut pool = Pool::new();
+        let ptrs = (0..pool.block_size() + 3)
+            .map(|i| pool.alloc(i))
+            .collect::<Vec<_>>();
+        pool.free(ptrs[pool.block_size() + 1]);
+        let (copy, remap) = pool.copy_block_to_new_pool(1);
+        copy.assert_consistent();
+        assert_eq!(copy.len(), 2);
+        assert_eq!(copy.capacity(), pool.block_size());
+        for &i in &[pool.block_size(), pool.block_size() + 2] {
+            assert_eq!(*copy.get(remap[&ptrs[i]]).unwrap(), i);
+        }
+        assert_eq!(pool.len(), pool.block_size() + 2);
+
+        let mut pool = Pool::with_block_size(2048);
+        let ptrs = (0..2048).map(|i| pool.alloc(i)).collect::<Vec<_>>();
+        let (copy, remap) = pool.copy_block_to_new_pool(0);
+        copy.assert_consistent();
+        assert_eq!(
+            (copy.len(), copy.capacity(), copy.block_size()),
+            (2048, 2048, 2048)
+        );
+        assert_eq!(*copy.get(remap[&ptrs[2047]]).unwrap(), 2047);
+
+        let mut pool = PoolBuilder::new()
+            .block_size(2)
+            .growth(GrowthPolicy::Doubling)
+            .build();
+        let ptrs = (0..14).map(|i| pool.alloc(i)).collect::<Vec<_>>();
+        let (copy, remap) = pool.copy_block_to_new_pool(2);
+        copy.assert_consistent();
+        assert_eq!((copy.len(), copy.capacity()), (8, 8));
+        assert!((6..14).all(|i| *copy.get(remap[&ptrs[i]]).unwrap() == i));
+        let (copy, _) = pool.copy_block_to_new_pool(0);
+        assert_eq!((copy.len(), copy.capacity()), (2, 2));
+    }
+
+    #[test]
+    fn take() {
+        let mut pool = Pool::new();
+        let ptr = pool.alloc(String::from("a"));
+        assert_eq!(pool.take(ptr), Some(String::from("a")));
+        assert_eq!(pool.take(ptr), None);
+        assert!(pool.is_empty());
+    }
+
+    #[test]
+    fn take_batch() {
+        let mut pool = Pool::new();
+        let other = Pool::new().alloc(String::new());
+        let ptrs = (0..5)
+            .map(|i| pool.alloc(i.to_string()))
+            .collect::<Vec<_>>();
+        pool.free(ptrs[1]);
+        let values = pool.take_batch(&[ptrs[0], ptrs[1], other, ptrs[3], ptrs[3]]);
+        assert_eq!(
+            values,
+            vec![Some("0".into()), None, None, Some("3".into()), None]
+        );
+        assert_eq!(pool.len(), 2);
+        pool.assert_consistent();
+    }
+
+    #[test]
+    fn replace_batch() {
+        let mut pool = Pool::new();
+        let ptrs = (0..4).map(|i| pool.alloc(i)).collect::<Vec<_>>();
+        pool.free(ptrs[2]);
+        let old = pool.replace_batch(&ptrs, vec![10, 11, 12, 13]);
+        assert_eq!(old, vec![Some(0), Some(1), None, Some(3)]);
+        assert_eq!(*pool.get(ptrs[3]).unwrap(), 13);
+        assert!(pool.get(ptrs[2]).is_none());
+    }
+
+    #[test]
+    fn replace_and_swap() {
+        let mut pool = Pool::new();
+        let ptrs = (0..3).map(|i| pool.alloc(i)).collect::<Vec<_>>();
+        assert_eq!(pool.replace(ptrs[0], 10), Some(0));
+        assert!(pool.swap(ptrs[0], ptrs[1]));
+        assert_eq!((pool[ptrs[0]], pool[ptrs[1]]), (1, 10));
+        assert!(pool.swap(ptrs[2], ptrs[2]));
+        assert_eq!(pool[ptrs[2]], 2);
+        pool.free(ptrs[2]);
+        assert_eq!(pool.replace(ptrs[2], 20), None);
+        assert!(!pool.swap(ptrs[0], ptrs[2]));
+        assert_eq!(pool[ptrs[0]], 1);
+        assert_eq!(pool.len(), 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "differ")]
+    fn replace_batch_mismatch() {
+        let mut pool = Pool::new();
+        let ptr = pool.alloc(0);
+        pool.replace_batch(&[ptr], vec![1, 2]);
+    }
+
+    #[test]
+    fn load_factor() {
+        let mut pool = Pool::new();
+        assert_eq!(pool.load_factor(), 0.0);
+        for i in 0..pool.block_size() / 4 {
+            pool.alloc(i);
+        }
+        assert_eq!(pool.load_factor(), 0.25);
+        assert_eq!(pool.occupancy(), 0.25);
+        assert_eq!(pool.len(), pool.capacity() / 4);
+        assert!(pool.occupied_ratio_exceeds(0.25));
+        assert!(!pool.occupied_ratio_exceeds(0.75));
+    }
+
+    #[test]
+    fn flatten_to_indexed_vec() {
+        let mut pool = Pool::new();
+        let ptrs = (0..3).map(|i| pool.alloc(i)).collect::<Vec<_>>();
+        pool.free(ptrs[1]);
+        let (values, indices) = pool.flatten_to_indexed_vec();
+        assert_eq!(values, vec![2, 0]);
+        assert_eq!(indices.len(), 1024);
+        assert_eq!(indices[1021..], [Some(0), None, Some(1)]);
+        assert!(indices[..1021].iter().all(Option::is_none));
+    }
+
+    #[cfg(feature = "debug-checks")]
+    #[test]
+    #[should_panic(expected = "already dropped")]
+    fn dropped_pool() {
+        let p = Pool::new().alloc(0);
+        unsafe { p.as_ref() };
+    }
+
+    #[test]
+    fn stale_ptr() {
+        let mut pool = Pool::new();
+        let p1 = pool.alloc(1);
+        assert!(pool.free(p1));
+        let p2 = pool.alloc(2);
+        assert_eq!(p1.ptr, p2.ptr);
+        assert_ne!(p1, p2);
+        assert!(pool.get(p1).is_none());
+        assert!(pool.get_mut(p1).is_none());
+        assert!(!pool.free(p1));
+        assert_eq!(*pool.get(p2).unwrap(), 2);
+        assert_eq!(Ptr::from(pool.get(p2).unwrap()), p2);
+        pool.free_all();
+        let p3 = pool.alloc(3);
+        assert!(pool.get(p2).is_none());
+        assert_eq!(*pool.get(p3).unwrap(), 3);
+    }
+
+    #[test]
+    fn retain_after_panic() {
+        let mut pool = PoolBuilder::new()
+            .free_list(FreeListPolicy::LowestFirst)
+            .build();
+        let ptrs = (0..4)
+            .map(|i| pool.alloc(PanicOnDrop(i)))
+            .collect::<Vec<_>>();
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            pool.retain(|_, value| value.0 == 3)
+        }));
+        assert!(result.is_err());
+        assert_eq!(pool.len(), 1);
+        pool.assert_consistent();
+        assert!(pool.get(ptrs[2]).is_none());
+        assert_eq!(pool.get(ptrs[3]).unwrap().0, 3);
+        assert_eq!(pool.free_list_head().unwrap().ptr, ptrs[0].ptr);
+    }
+
+    #[test]
+    fn retain() {
+        let mut pool = Pool::new();
+        let ptrs = (0..2000).map(|i| pool.alloc(i)).collect::<Vec<_>>();
+        pool.retain(|p, i| {
+            assert_eq!(p, ptrs[*i]);
+            *i += 1;
+            *i % 3 == 0
+        });
+        pool.assert_consistent();
+        assert_eq!(pool.len(), 666);
+        assert!(pool.get(ptrs[0]).is_none());
+        assert_eq!(*pool.get(ptrs[2]).unwrap(), 3);
+    }
+
+    #[test]
+    fn ptr_traits() {
+        use std::collections::hash_map::DefaultHasher;
+        use std::hash::{Hash, Hasher};
+
+        let mut pool = Pool::new();
+        let p = pool.alloc(0);
+        // the same entry seen from another pool, e.g. a block reused after its pool is dropped
+        let q = Ptr {
+            pool_id: PoolId::gen(),
+            ..p
+        };
+        let r = Ptr { generation: 1, ..p };
+        assert_ne!(p, q);
+        assert_ne!(p.cmp(&q), core::cmp::Ordering::Equal);
+        assert_ne!(p.cmp(&r), core::cmp::Ordering::Equal);
+        let hash = |p: Ptr<i32>| {
+            let mut hasher = DefaultHasher::new();
+            p.hash(&mut hasher);
+            hasher.finish()
+        };
+        assert_ne!(hash(p), hash(q));
+        assert_eq!(hash(p), hash(Ptr { ..p }));
+        let set = [p, q, r, p]
+            .iter()
+            .copied()
+            .collect::<std::collections::BTreeSet<_>>();
+        assert_eq!(set.len(), 3);
+    }
+
+    #[test]
+    fn dangling() {
+        let mut pool = Pool::new();
+        let mut p = Ptr::DANGLING;
+        assert!(p.is_dangling());
+        assert!(unsafe { p.as_ref() }.is_none() && unsafe { p.as_mut() }.is_none());
+        assert!(pool.get(p).is_none() && pool.get_mut(p).is_none());
+        assert!(!pool.free(p) && pool.take(p).is_none());
+        p = pool.alloc(1);
+        assert!(!p.is_dangling());
+        assert_eq!(*pool.get(p).unwrap(), 1);
+    }
+
+    #[test]
+    fn free_where() {
+        let mut pool = Pool::with_block_size(16);
+        let ptrs = (0..100).map(|i| pool.alloc(i)).collect::<Vec<_>>();
+        assert_eq!(pool.free_where(|&i| i < 30), 30);
+        assert_eq!(pool.free_where(|&i| i < 30), 0);
+        pool.assert_consistent();
+        assert_eq!(pool.len(), 70);
+        assert!(ptrs[..30].iter().all(|&p| pool.get(p).is_none()));
+        assert!(ptrs[30..].iter().all(|&p| pool.get(p).is_some()));
+    }
+
+    #[test]
+    fn clear() {
+        let mut pool = Pool::new();
+        let old = (0..10).map(|i| pool.alloc(i)).collect::<Vec<_>>();
+        pool.clear();
+        assert!(pool.is_empty());
+        let new = (0..10).map(|i| pool.alloc(i)).collect::<Vec<_>>();
+        assert_eq!(pool.blocks.len(), 1);
+        assert!(old.iter().all(|&p| pool.get(p).is_none()));
+        assert!(new.iter().all(|&p| pool.get(p).is_some()));
+    }
+
+    #[test]
+    fn weak_ptr() {
+        let mut pool = Pool::new();
+        let mut other = Pool::new();
+        let p = pool.alloc(1);
+        let w = p.downgrade();
+        assert_eq!(w.upgrade(&pool), Some(p));
+        assert_eq!(*w.upgrade_ref(&pool).unwrap(), 1);
+        assert!(w.is_alive(&pool));
+        other.alloc(1);
+        assert!(!w.is_alive(&other));
+        pool.free(p);
+        assert!(!w.is_alive(&pool));
+        let q = pool.alloc(2);
+        assert_eq!(q.ptr, p.ptr);
+        assert_eq!(w.upgrade(&pool), None);
+
+        let mut aligned = Pool::with_block_size_in(4, Aligned::new(64));
+        let w = aligned.alloc(3).downgrade();
+        assert_eq!(*w.upgrade_ref(&aligned).unwrap(), 3);
+        assert!(w.is_alive(&aligned) && !w.is_alive(&pool));
+    }
+
+    #[test]
+    fn with_block_size() {
+        let mut pool = Pool::with_block_size(3);
+        assert_eq!(pool.block_size(), 3);
+        let ptrs = (0..7).map(|i| pool.alloc(i)).collect::<Vec<_>>();
+        assert_eq!(pool.blocks.len(), 3);
+        assert_eq!(pool.capacity(), 9);
+        assert!(ptrs
+            .iter()
+            .enumerate()
+            .all(|(i, &p)| *pool.get(p).unwrap() == i));
+        pool.assert_consistent();
+    }
+
+    #[test]
+    fn ptr_repr() {
+        let mut pool = Pool::with_block_size(2);
+        let ptrs = (0..5).map(|i| pool.alloc(i)).collect::<Vec<_>>();
+        pool.free(ptrs[1]);
+        let p = pool.alloc(10);
+        let repr = pool.ptr_to_repr(p).unwrap();
+        assert_eq!(repr.generation, 1);
+        assert_eq!(pool.ptr_from_repr(repr), Some(p));
+        for &p in &ptrs {
+            let repr = pool.ptr_to_repr(p).unwrap();
+            assert_eq!(pool.ptr_from_repr(repr), Some(p));
+        }
+        assert_eq!(pool.ptr_to_repr(Ptr::DANGLING), None);
+        assert_eq!(
+            pool.ptr_from_repr(PtrRepr {
+                block: 3,
+                slot: 1,
+                generation: 0
+            }),
+            None
+        );
+    }
+
+    #[test]
+    fn raw_ptr() {
+        let mut pool = Pool::new();
+        let p = pool.alloc(1);
+        extern "C" fn callback(raw: *const RawPtr) -> u32 {
+            unsafe { (*raw).generation }
+        }
+        let raw = p.into_raw();
+        assert_eq!(callback(&raw), 0);
+        let q = unsafe { Ptr::<i32>::from_raw(raw) };
+        assert_eq!(q, p);
+        assert_eq!(pool[q], 1);
+        pool.free(p);
+        assert!(pool.get(unsafe { Ptr::from_raw(raw) }).is_none());
+    }
+
+    #[test]
+    fn index() {
+        let mut pool = Pool::new();
+        let p = pool.alloc(1);
+        pool[p] += 1;
+        assert_eq!(pool[p], 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "refers to a vacant entry")]
+    fn index_vacant() {
+        let mut pool = Pool::new();
+        let p = pool.alloc(1);
+        pool.free(p);
+        let _ = pool[p];
+    }
+
+    #[test]
+    #[should_panic(expected = "belongs to pool")]
+    fn index_wrong_pool() {
+        let mut pool = Pool::new();
+        let p = pool.alloc(1);
+        let _ = Pool::<i32>::new()[p];
+    }
+
+    #[test]
+    fn get_disjoint_mut() {
+        let mut pool = Pool::new();
+        let ptrs = (0..4).map(|i| pool.alloc(i)).collect::<Vec<_>>();
+        let [a, b, c] = pool.get_disjoint_mut([ptrs[2], ptrs[0], ptrs[3]]).unwrap();
+        core::mem::swap(a, b);
+        *c += 10;
+        assert_eq!(pool.iter().map(|(_, &i)| i).sum::<i32>(), 16);
+        assert_eq!((pool[ptrs[0]], pool[ptrs[2]]), (2, 0));
+        let (a, b) = pool.get2_mut(ptrs[1], ptrs[3]).unwrap();
+        *a += *b;
+        assert_eq!(pool[ptrs[1]], 14);
+        assert!(pool.get2_mut(ptrs[1], ptrs[1]).is_none());
+        pool.free(ptrs[1]);
+        let q = pool.alloc(5);
+        assert!(pool.get2_mut(ptrs[1], q).is_none());
+        assert!(pool.get_disjoint_mut([ptrs[0], ptrs[1]]).is_none());
+        assert!(pool.get_disjoint_mut([]).is_some());
+    }
+
+    #[test]
+    fn unchecked() {
+        let mut pool = Pool::new();
+        let p = pool.alloc(1);
+        unsafe {
+            *pool.get_unchecked_mut(p).unwrap() += 1;
+            assert_eq!(*pool.get_unchecked(p).unwrap(), 2);
+            assert!(pool.free_unchecked(p));
+            assert!(!pool.free_unchecked(p));
+            assert!(pool.get_unchecked(p).is_none());
+        }
+    }
+
+    #[test]
+    fn try_access() {
+        let mut pool = Pool::new();
+        let mut other = Pool::<i32>::new();
+        let p = pool.alloc(1);
+        assert_eq!(*pool.try_get(p).unwrap(), 1);
+        *pool.try_get_mut(p).unwrap() = 2;
+        assert_eq!(
+            other.try_free(p),
+            Err(PoolError::WrongPool {
+                expected: other.id(),
+                found: pool.id()
+            })
+        );
+        assert_eq!(pool.try_free(p), Ok(()));
+        assert_eq!(pool.try_get(p).unwrap_err(), PoolError::Vacant);
+        let q = pool.alloc(3);
+        assert_eq!(q.ptr, p.ptr);
+        assert_eq!(
+            pool.try_get_mut(p).unwrap_err(),
+            PoolError::StaleGeneration {
+                expected: 0,
+                found: 1
+            }
+        );
+        assert_eq!(*pool.try_get(q).unwrap(), 3);
+    }
+
+    #[test]
+    fn shrink_to_fit() {
+        let mut pool = Pool::with_block_size(2);
+        let ptrs = (0..6).map(|i| pool.alloc(i)).collect::<Vec<_>>();
+        let (freed, kept): (Vec<_>, Vec<_>) = ptrs
+            .into_iter()
+            .partition(|&p| pool.ptr_to_repr(p).unwrap().block == 1);
+        pool.free(freed[0]);
+        assert_eq!(unsafe { pool.shrink_to_fit() }, 0);
+        pool.free(freed[1]);
+        assert_eq!(unsafe { pool.shrink_to_fit() }, 1);
+        assert_eq!(pool.capacity(), 4);
+        pool.assert_consistent();
+        assert!(kept.iter().all(|&p| pool.get(p).is_some()));
+        pool.free(kept[0]);
+        let p = pool.alloc(10);
+        assert_eq!(p.ptr, kept[0].ptr);
+        assert_eq!(pool.capacity(), 4);
+    }
+
+    struct Node {
+        next: Option<Ptr<Node>>,
+        prev: Option<Ptr<Node>>,
+    }
+
+    #[test]
+    fn graph() {
+        let mut pool = Pool::new();
+        let h1 = pool.alloc(Node {
+            next: None,
+            prev: None,
+        });
+        let h2 = pool.alloc(Node {
+            next: None,
+            prev: None,
+        });
+        assert_ne!(h1, h2);
+        pool.get_mut(h1).unwrap().next = Some(h2);
+        pool.get_mut(h2).unwrap().prev = Some(h1);
+
+        let mut map = std::collections::HashSet::new();
+        map.insert(h1);
+        map.insert(h2);
+
+        let mut tree = std::collections::BTreeSet::new();
+        tree.insert(h1);
+        tree.insert(h2);
+    }
+
+    #[test]
+    fn option_ptr_size() {
+        use core::mem::size_of;
+        assert_eq!(size_of::<Option<Ptr<Node>>>(), size_of::<Ptr<Node>>());
+        assert_eq!(
+            size_of::<Node>(),
+            2 * size_of::<Ptr<Node>>(),
+            "links of Node pay no space for None"
+        );
+    }
+
+    #[test]
+    fn alloc_cyclic() {
+        let mut pool = Pool::new();
+        let h = pool.alloc_cyclic(|p| Node {
+            next: Some(p),
+            prev: Some(p),
+        });
+        assert_eq!(pool.get(h).unwrap().next, Some(h));
+        assert_eq!(pool.get(h).unwrap().prev, Some(h));
+    }
+
+    #[test]
+    fn vacant_entry() {
+        let mut pool = Pool::with_block_size(1);
+        let a = pool.alloc(Node {
+            next: None,
+            prev: None,
+        });
+        let entry = pool.vacant_entry();
+        let b = entry.ptr();
+        assert_eq!(
+            entry.insert(Node {
+                next: Some(a),
+                prev: Some(b),
+            }),
+            b
+        );
+        pool[a].prev = Some(b);
+        assert_eq!(pool[pool[a].prev.unwrap()].next, Some(a));
+        assert_eq!(pool[b].prev, Some(b));
+        assert_eq!(pool.len(), 2);
+        pool.assert_consistent();
+    }
+
+    #[test]
+    fn alloc_and_link() {
+        let mut pool = Pool::new();
+        let h1 = pool.alloc_and_link(|prev, next| Node { prev, next }, None, None);
+        let h2 = pool.alloc_and_link(|prev, next| Node { prev, next }, Some(h1), None);
+        pool.get_mut(h1).unwrap().next = Some(h2);
+        assert_eq!(pool.get(h2).unwrap().prev, Some(h1));
+        assert_eq!(pool.get(h1).unwrap().next, Some(h2));
     }
 
     struct Node2<'a> {
@@ -298,6 +3262,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::option_as_ref_deref)]
     fn graph2() {
         let mut pool = Pool::new();
         let h1 = pool.alloc(Node2 {
@@ -0,0 +1,199 @@
+use crate::{Pool, Ptr};
+
+/// A node of `LinkedList`, which holds a value and the links to its neighbors.
+#[derive(Debug)]
+pub struct ListNode<T> {
+    value: T,
+    prev: Option<Ptr<ListNode<T>>>,
+    next: Option<Ptr<ListNode<T>>>,
+}
+
+impl<T> ListNode<T> {
+    pub fn value(&self) -> &T {
+        &self.value
+    }
+}
+
+/// A doubly-linked list whose nodes are allocated in a `Pool`.
+/// A node is referred to by `Ptr<ListNode<T>>`, which stays valid until the node is removed,
+/// so that a value can be inserted or removed anywhere in O(1).
+#[derive(Debug)]
+pub struct LinkedList<T> {
+    pool: Pool<ListNode<T>>,
+    head: Option<Ptr<ListNode<T>>>,
+    tail: Option<Ptr<ListNode<T>>>,
+}
+
+impl<T> LinkedList<T> {
+    pub fn new() -> Self {
+        Self {
+            pool: Pool::new(),
+            head: None,
+            tail: None,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.pool.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pool.is_empty()
+    }
+
+    pub fn front(&self) -> Option<Ptr<ListNode<T>>> {
+        self.head
+    }
+
+    pub fn back(&self) -> Option<Ptr<ListNode<T>>> {
+        self.tail
+    }
+
+    pub fn next(&self, p: Ptr<ListNode<T>>) -> Option<Ptr<ListNode<T>>> {
+        self.pool.get(p)?.next
+    }
+
+    pub fn prev(&self, p: Ptr<ListNode<T>>) -> Option<Ptr<ListNode<T>>> {
+        self.pool.get(p)?.prev
+    }
+
+    pub fn get(&self, p: Ptr<ListNode<T>>) -> Option<&T> {
+        self.pool.get(p).map(|node| &node.get().value)
+    }
+
+    pub fn get_mut(&mut self, p: Ptr<ListNode<T>>) -> Option<&mut T> {
+        self.pool.get_mut(p).map(|node| &mut node.value)
+    }
+
+    pub fn push_front(&mut self, value: T) -> Ptr<ListNode<T>> {
+        self.insert_between(None, self.head, value)
+    }
+
+    pub fn push_back(&mut self, value: T) -> Ptr<ListNode<T>> {
+        self.insert_between(self.tail, None, value)
+    }
+
+    pub fn pop_front(&mut self) -> Option<T> {
+        self.remove(self.head?)
+    }
+
+    pub fn pop_back(&mut self) -> Option<T> {
+        self.remove(self.tail?)
+    }
+
+    /// Inserts `value` next to `at`, or returns `None` if `at` is already removed.
+    pub fn insert_after(&mut self, at: Ptr<ListNode<T>>, value: T) -> Option<Ptr<ListNode<T>>> {
+        let next = self.pool.get(at)?.next;
+        Some(self.insert_between(Some(at), next, value))
+    }
+
+    /// Inserts `value` previous to `at`, or returns `None` if `at` is already removed.
+    pub fn insert_before(&mut self, at: Ptr<ListNode<T>>, value: T) -> Option<Ptr<ListNode<T>>> {
+        let prev = self.pool.get(at)?.prev;
+        Some(self.insert_between(prev, Some(at), value))
+    }
+
+    fn insert_between(
+        &mut self,
+        prev: Option<Ptr<ListNode<T>>>,
+        next: Option<Ptr<ListNode<T>>>,
+        value: T,
+    ) -> Ptr<ListNode<T>> {
+        let p = self.pool.alloc(ListNode { value, prev, next });
+        self.link(prev, Some(p));
+        self.link(Some(p), next);
+        p
+    }
+
+    /// Makes `b` the next of `a`, where `None` stands for the end of the list.
+    fn link(&mut self, a: Option<Ptr<ListNode<T>>>, b: Option<Ptr<ListNode<T>>>) {
+        match a {
+            Some(a) => self.pool[a].next = b,
+            None => self.head = b,
+        }
+        match b {
+            Some(b) => self.pool[b].prev = a,
+            None => self.tail = a,
+        }
+    }
+
+    /// Removes the node of `p` and returns its value, or returns `None` if it is already removed.
+    pub fn remove(&mut self, p: Ptr<ListNode<T>>) -> Option<T> {
+        let node = self.pool.take(p)?;
+        self.link(node.prev, node.next);
+        Some(node.value)
+    }
+
+    /// Moves the nodes from `first` to `last` inclusive next to `at`, or to the front if `at` is `None`.
+    /// `first` must not be after `last`, and `at` must not be among them.
+    ///
+    /// # Panics
+    /// Panics if `first` or `last` is already removed.
+    pub fn splice_after(
+        &mut self,
+        at: Option<Ptr<ListNode<T>>>,
+        first: Ptr<ListNode<T>>,
+        last: Ptr<ListNode<T>>,
+    ) {
+        let (prev, next) = (self.pool[first].prev, self.pool[last].next);
+        self.link(prev, next);
+        let after = match at {
+            Some(at) => self.pool[at].next,
+            None => self.head,
+        };
+        self.link(at, Some(first));
+        self.link(Some(last), after);
+    }
+
+    /// Iterates over the pointers and the values from the front to the back.
+    pub fn iter(&self) -> impl Iterator<Item = (Ptr<ListNode<T>>, &T)> {
+        let mut cursor = self.head;
+        core::iter::from_fn(move || {
+            let p = cursor?;
+            let node = self.pool.get(p).unwrap().get();
+            cursor = node.next;
+            Some((p, &node.value))
+        })
+    }
+}
+
+impl<T> Default for LinkedList<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[test]
+fn test() {
+    use alloc::vec::Vec;
+
+    let mut list = LinkedList::new();
+    let values = |list: &LinkedList<i32>| list.iter().map(|(_, &v)| v).collect::<Vec<_>>();
+    let b = list.push_back(2);
+    let a = list.push_front(1);
+    let d = list.push_back(4);
+    let c = list.insert_after(b, 3).unwrap();
+    assert_eq!(values(&list), [1, 2, 3, 4]);
+    assert_eq!(list.insert_before(a, 0), list.front());
+    assert_eq!((list.next(b), list.prev(b)), (Some(c), Some(a)));
+
+    list.splice_after(Some(d), a, b);
+    assert_eq!(values(&list), [0, 3, 4, 1, 2]);
+    list.splice_after(None, d, b);
+    assert_eq!(values(&list), [4, 1, 2, 0, 3]);
+    assert_eq!(list.back(), Some(c));
+
+    assert_eq!(list.remove(a), Some(1));
+    assert_eq!(list.remove(a), None);
+    assert_eq!(list.insert_after(a, 10), None);
+    *list.get_mut(b).unwrap() *= 10;
+    assert_eq!(list.get(b), Some(&20));
+    assert_eq!(list.pop_front(), Some(4));
+    assert_eq!(list.pop_back(), Some(3));
+    assert_eq!(values(&list), [20, 0]);
+    assert_eq!(list.len(), 2);
+    assert_eq!(list.pop_back(), Some(0));
+    assert_eq!(list.pop_back(), Some(20));
+    assert!(list.is_empty());
+    assert_eq!((list.front(), list.back()), (None, None));
+}
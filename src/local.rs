@@ -0,0 +1,264 @@
+use crate::{Pool, PoolId, Ptr, RemoteFree};
+use std::cell::Cell;
+use std::sync::RwLock;
+use std::thread::ThreadId;
+
+struct Shard<T> {
+    owner: ThreadId,
+    id: PoolId,
+    pool: RwLock<Pool<T>>,
+    remote: RemoteFree<T>,
+}
+
+// The pool of a shard is created without an observer, so it holds only `T` and its own blocks.
+unsafe impl<T: Send> Send for Shard<T> {}
+unsafe impl<T: Send + Sync> Sync for Shard<T> {}
+
+thread_local! {
+    /// The id of the `ThreadLocalPool` last used by the thread, and the shard of the thread in it.
+    /// The ids are never reused, so the shard of a dropped pool is never found.
+    static LAST: Cell<Option<(PoolId, *const ())>> = const { Cell::new(None) };
+}
+
+/// A pool shared by threads, each of which allocates from its own shard.
+/// The owner of a shard is the only writer of it, so allocation is not contended by other threads.
+///
+/// A `Ptr` can be read from any thread. Freeing it from a thread other than the owner is routed
+/// back to the owner by `RemoteFree`, and applied on the next allocation of the owner or `collect`.
+/// The values are read by `get` or `with`, which hold the shard of the value only until they return.
+/// Allocation takes no lock but the one of the shard of the current thread, which is cached by the thread,
+/// so it waits only for the reads of the values of the thread.
+pub struct ThreadLocalPool<T> {
+    id: PoolId,
+    /// The shards, which are boxed and never removed until the pool is dropped.
+    /// Threads are few, so they are looked up linearly.
+    #[allow(clippy::vec_box)]
+    shards: RwLock<Vec<Box<Shard<T>>>>,
+    block_size: usize,
+}
+
+impl<T> ThreadLocalPool<T> {
+    pub fn new() -> Self {
+        Self::with_block_size(Pool::<T>::DEFAULT_BLOCK_SIZE)
+    }
+
+    /// Creates a pool whose shards have blocks of `block_size` entries.
+    pub fn with_block_size(block_size: usize) -> Self {
+        Self {
+            id: PoolId::gen(),
+            shards: RwLock::new(Vec::new()),
+            block_size,
+        }
+    }
+
+    fn find(&self, f: impl Fn(&Shard<T>) -> bool) -> Option<&Shard<T>> {
+        let shards = self.shards.read().unwrap();
+        let shard: &Shard<T> = shards.iter().find(|shard| f(shard))?;
+        // a boxed shard does not move, and lives as long as `self`
+        Some(unsafe { &*(shard as *const Shard<T>) })
+    }
+
+    /// Returns the shard of the current thread if cached by the thread.
+    fn cached(&self) -> Option<&Shard<T>> {
+        match LAST.with(Cell::get) {
+            // the shard lives as long as `self`, which has the id
+            Some((id, shard)) if id == self.id => Some(unsafe { &*shard.cast::<Shard<T>>() }),
+            _ => None,
+        }
+    }
+
+    /// Returns the shard of the current thread if it has allocated from this pool.
+    fn find_local(&self) -> Option<&Shard<T>> {
+        if let Some(shard) = self.cached() {
+            return Some(shard);
+        }
+        let owner = std::thread::current().id();
+        let shard = self.find(|shard| shard.owner == owner)?;
+        LAST.with(|last| last.set(Some((self.id, shard as *const Shard<T> as *const ()))));
+        Some(shard)
+    }
+
+    /// Returns the shard of the current thread, creating it on the first allocation of the thread.
+    fn local(&self) -> &Shard<T> {
+        if let Some(shard) = self.find_local() {
+            return shard;
+        }
+        let owner = std::thread::current().id();
+        let mut pool = Pool::with_block_size(self.block_size);
+        let remote = pool.remote_free();
+        let shard = Box::new(Shard {
+            owner,
+            id: pool.id(),
+            pool: RwLock::new(pool),
+            remote,
+        });
+        let shard_ptr = &*shard as *const Shard<T>;
+        self.shards.write().unwrap().push(shard);
+        LAST.with(|last| last.set(Some((self.id, shard_ptr as *const ()))));
+        unsafe { &*shard_ptr }
+    }
+
+    /// Returns the shard to which `p` belongs.
+    ///
+    /// # Panics
+    /// Panics if `p` does not belong to this pool.
+    fn owner_of(&self, p: Ptr<T>) -> &Shard<T> {
+        if let Some(shard) = self.cached().filter(|shard| shard.id == p.pool_id) {
+            return shard;
+        }
+        self.find(|shard| shard.id == p.pool_id)
+            .expect("pointer does not belong to this pool")
+    }
+
+    /// Number of the shards, that is, the threads which have allocated from this pool.
+    pub fn shard_count(&self) -> usize {
+        self.shards.read().unwrap().len()
+    }
+
+    /// Number of values of all shards, including the ones scheduled to be freed but not collected yet.
+    pub fn len(&self) -> usize {
+        let shards = self.shards.read().unwrap();
+        shards
+            .iter()
+            .map(|shard| shard.pool.read().unwrap().len())
+            .sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Allocates `value` in the shard of the current thread.
+    pub fn alloc(&self, value: T) -> Ptr<T> {
+        let mut pool = self.local().pool.write().unwrap();
+        pool.collect_remote_frees();
+        pool.alloc(value)
+    }
+
+    /// Returns a clone of the value of `p`, or `None` if `p` is already freed.
+    ///
+    /// # Panics
+    /// Panics if `p` does not belong to this pool.
+    pub fn get(&self, p: Ptr<T>) -> Option<T>
+    where
+        T: Clone,
+    {
+        self.with(p, T::clone)
+    }
+
+    /// Calls `f` with the value of `p` and returns its result, or returns `None` if `p` is already freed.
+    /// The shard of `p` is kept from being written while `f` runs.
+    ///
+    /// # Panics
+    /// Panics if `p` does not belong to this pool.
+    /// If `f` allocates or frees with this pool on the thread owning `p`, it deadlocks instead.
+    pub fn with<R>(&self, p: Ptr<T>, f: impl FnOnce(&T) -> R) -> Option<R> {
+        let pool = self.owner_of(p).pool.read().unwrap();
+        pool.get(p).map(|value| f(&value))
+    }
+
+    /// Frees `p` if the current thread owns it, or schedules freeing it otherwise.
+    /// Freeing a pointer already freed is ignored.
+    ///
+    /// # Panics
+    /// Panics if `p` does not belong to this pool.
+    pub fn free(&self, p: Ptr<T>) {
+        let shard = self.owner_of(p);
+        if shard.owner == std::thread::current().id() {
+            shard.pool.write().unwrap().free(p);
+        } else {
+            shard.remote.free(p);
+        }
+    }
+
+    /// Applies the frees scheduled to the shard of the current thread, and returns how many values are freed.
+    pub fn collect(&self) -> usize {
+        match self.find_local() {
+            Some(shard) => shard.pool.write().unwrap().collect_remote_frees(),
+            None => 0,
+        }
+    }
+}
+
+impl<T> Default for ThreadLocalPool<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> std::fmt::Debug for ThreadLocalPool<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "ThreadLocalPool {{ shards: {} }}", self.shard_count())
+    }
+}
+
+#[test]
+fn test() {
+    let pool = ThreadLocalPool::with_block_size(16);
+    let main = (0..10).map(|i| pool.alloc(i)).collect::<Vec<_>>();
+    let others = std::thread::scope(|s| {
+        let handles = (0..3)
+            .map(|t| {
+                let (pool, main) = (&pool, &main);
+                s.spawn(move || {
+                    assert_eq!(pool.get(main[t]), Some(t));
+                    // freed by the main thread on its next allocation
+                    pool.free(main[t]);
+                    (0..10).map(|i| pool.alloc(100 * t + i)).collect::<Vec<_>>()
+                })
+            })
+            .collect::<Vec<_>>();
+        handles
+            .into_iter()
+            .map(|h| h.join().unwrap())
+            .collect::<Vec<_>>()
+    });
+    assert_eq!(pool.shard_count(), 4);
+    assert_eq!(pool.len(), 40);
+    assert!(pool.get(main[0]).is_some());
+    assert_eq!(pool.collect(), 3);
+    assert!(pool.get(main[0]).is_none());
+    assert_eq!(pool.get(others[2][5]), Some(205));
+
+    // frees to the shards of finished threads stay scheduled, since their owners never collect them
+    pool.free(others[0][0]);
+    assert_eq!(pool.len(), 37);
+    pool.free(main[9]);
+    assert_eq!(pool.len(), 36);
+    assert_eq!(pool.with(main[8], |i| i.to_string()).as_deref(), Some("8"));
+}
+
+#[test]
+fn read_while_allocating() {
+    let pool = ThreadLocalPool::new();
+    let a = pool.alloc(String::from("a"));
+    let r = pool.get(a).unwrap();
+    let b = pool.alloc(r + "b");
+    pool.free(a);
+    assert_eq!(pool.get(b).as_deref(), Some("ab"));
+    assert_eq!(pool.with(a, String::len), None);
+}
+
+#[test]
+fn cached_shard() {
+    let (pool, other) = (ThreadLocalPool::new(), ThreadLocalPool::new());
+    let a = pool.alloc(0);
+    let b = other.alloc(1);
+    assert!(pool.cached().is_none());
+    assert_eq!(pool.collect(), 0);
+    // the shard of the thread is cached, so the shards are not looked up
+    let shards = pool.shards.write().unwrap();
+    let c = pool.alloc(2);
+    pool.free(a);
+    assert_eq!(pool.with(c, |&i| i + 1), Some(3));
+    drop(shards);
+    assert_eq!(other.get(b), Some(1));
+    assert_eq!(pool.len(), 1);
+}
+
+#[test]
+#[should_panic]
+fn wrong_pool() {
+    let pool = ThreadLocalPool::new();
+    ThreadLocalPool::new().get(pool.alloc(0));
+}
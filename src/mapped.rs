@@ -0,0 +1,376 @@
+use crate::{BlockStorage, Entry, Pool};
+use core::alloc::Layout;
+use core::cell::Cell;
+use core::convert::{TryFrom, TryInto};
+use core::ops::{Deref, DerefMut};
+use core::ptr::NonNull;
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::os::unix::fs::FileExt;
+use std::os::unix::io::AsRawFd;
+use std::path::Path;
+
+mod sys {
+    use std::os::raw::{c_int, c_void};
+
+    pub const PROT_READ: c_int = 1;
+    pub const PROT_WRITE: c_int = 2;
+    pub const MAP_SHARED: c_int = 1;
+
+    extern "C" {
+        pub fn mmap(
+            addr: *mut c_void,
+            len: usize,
+            prot: c_int,
+            flags: c_int,
+            fd: c_int,
+            offset: i64,
+        ) -> *mut c_void;
+        pub fn munmap(addr: *mut c_void, len: usize) -> c_int;
+    }
+}
+
+const MAGIC: [u8; 8] = *b"mepoo\0\0\x01";
+/// The alignment of the blocks in the file, which is a multiple of the page sizes of the common platforms.
+/// The header occupies the first one.
+const BLOCK_ALIGN: usize = 1 << 16;
+
+/// The header at the start of the file: the magic, the size and the alignment of an entry,
+/// the number of entries of a block and the number of blocks, in native byte order.
+struct Header {
+    entry: Layout,
+    block_size: usize,
+    blocks: usize,
+}
+
+impl Header {
+    const LEN: usize = 40;
+
+    fn to_bytes(&self) -> [u8; Self::LEN] {
+        let mut bytes = [0; Self::LEN];
+        bytes[..8].copy_from_slice(&MAGIC);
+        let fields = [
+            self.entry.size(),
+            self.entry.align(),
+            self.block_size,
+            self.blocks,
+        ];
+        for (chunk, field) in bytes[8..].chunks_mut(8).zip(fields) {
+            chunk.copy_from_slice(&(field as u64).to_ne_bytes());
+        }
+        bytes
+    }
+
+    fn from_bytes(bytes: &[u8; Self::LEN]) -> io::Result<Self> {
+        let invalid = |msg| io::Error::new(io::ErrorKind::InvalidData, msg);
+        if bytes[..8] != MAGIC {
+            return Err(invalid("not a file of a mapped pool"));
+        }
+        let mut fields = bytes[8..].chunks(8).map(|chunk| {
+            let field = u64::from_ne_bytes(chunk.try_into().unwrap());
+            usize::try_from(field).map_err(|_| invalid("header is out of range"))
+        });
+        let mut next = || fields.next().unwrap();
+        let (size, align) = (next()?, next()?);
+        Ok(Self {
+            entry: Layout::from_size_align(size, align)
+                .map_err(|_| invalid("invalid entry layout"))?,
+            block_size: next()?,
+            blocks: next()?,
+        })
+    }
+}
+
+/// A storage which maps the blocks from a file, by which a pool of plain data persists across runs.
+/// It is created by `Pool::create_mapped` and reopened by `Pool::open_mapped`,
+/// and the values are identified over runs by `PtrRepr`, since the blocks are mapped in the same order.
+/// To keep the order, the blocks cannot be swapped or released, e.g. by `Pool::swap_blocks` or `Pool::shrink_to_fit`.
+///
+/// The values written reach the file through the shared mapping without an explicit flush,
+/// but are durable against a crash of the system only after the OS writes the pages back.
+#[derive(Debug)]
+pub struct Mapped {
+    file: File,
+    entry: Layout,
+    block_size: usize,
+    blocks: Cell<usize>,
+}
+
+impl Mapped {
+    fn header(&self) -> Header {
+        Header {
+            entry: self.entry,
+            block_size: self.block_size,
+            blocks: self.blocks.get(),
+        }
+    }
+
+    /// The number of bytes mapped for a block, rounded up to `BLOCK_ALIGN`.
+    fn block_len(&self) -> usize {
+        let len = self.entry.size() * self.block_size;
+        len.div_ceil(BLOCK_ALIGN) * BLOCK_ALIGN
+    }
+
+    fn block_offset(&self, k: usize) -> usize {
+        BLOCK_ALIGN + k * self.block_len()
+    }
+
+    /// Maps the `k`-th block, which must exist in the file.
+    fn map<T>(&self, k: usize) -> io::Result<MappedBlock<T>> {
+        let map_len = self.block_len();
+        let offset = i64::try_from(self.block_offset(k)).expect("file is too large");
+        let ptr = unsafe {
+            sys::mmap(
+                core::ptr::null_mut(),
+                map_len,
+                sys::PROT_READ | sys::PROT_WRITE,
+                sys::MAP_SHARED,
+                self.file.as_raw_fd(),
+                offset,
+            )
+        };
+        if ptr as usize == usize::MAX {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(MappedBlock {
+            ptr: NonNull::new(ptr.cast()).unwrap(),
+            len: self.block_size,
+            map_len,
+        })
+    }
+
+    fn grow<T>(&self) -> io::Result<MappedBlock<T>> {
+        let k = self.blocks.get();
+        self.file
+            .set_len((self.block_offset(k) + self.block_len()) as u64)?;
+        let mut block = self.map(k)?;
+        self.blocks.set(k + 1);
+        self.file.write_all_at(&self.header().to_bytes(), 0)?;
+        // the entries are not initialized yet
+        block.len = 0;
+        Ok(block)
+    }
+}
+
+unsafe impl BlockStorage for Mapped {
+    type Block<T> = MappedBlock<T>;
+
+    /// The blocks are mapped in the order of the file when reopened, which `PtrRepr` relies on.
+    const REORDERABLE: bool = false;
+
+    /// # Panics
+    /// Panics if `len` or the layout of the entry differs from the ones of the file, or the file fails to grow.
+    fn alloc_block<T>(&self, len: usize, mut f: impl FnMut() -> Entry<T>) -> MappedBlock<T> {
+        assert!(
+            len == self.block_size && Layout::new::<Entry<T>>() == self.entry,
+            "block does not fit the file of the mapped pool"
+        );
+        let mut block = self
+            .grow()
+            .unwrap_or_else(|err| panic!("failed to map a block: {}", err));
+        // the entries made so far are dropped by the block even if `f` panics
+        while block.len < len {
+            unsafe { block.ptr.as_ptr().add(block.len).write(f()) };
+            block.len += 1;
+        }
+        block
+    }
+}
+
+/// A block mapped by `Mapped`.
+pub struct MappedBlock<T> {
+    ptr: NonNull<Entry<T>>,
+    len: usize,
+    map_len: usize,
+}
+
+unsafe impl<T: Send> Send for MappedBlock<T> {}
+unsafe impl<T: Sync> Sync for MappedBlock<T> {}
+
+impl<T> Deref for MappedBlock<T> {
+    type Target = [Entry<T>];
+    fn deref(&self) -> &[Entry<T>] {
+        unsafe { core::slice::from_raw_parts(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl<T> DerefMut for MappedBlock<T> {
+    fn deref_mut(&mut self) -> &mut [Entry<T>] {
+        unsafe { core::slice::from_raw_parts_mut(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl<T> Drop for MappedBlock<T> {
+    fn drop(&mut self) {
+        unsafe {
+            core::ptr::drop_in_place(&mut **self as *mut [Entry<T>]);
+            sys::munmap(self.ptr.as_ptr().cast(), self.map_len);
+        }
+    }
+}
+
+impl<T: Copy> Pool<T, Mapped> {
+    /// Creates a pool whose blocks of `block_size` entries are mapped from the file at `path`,
+    /// which is created or truncated.
+    ///
+    /// # Panics
+    /// Panics if `block_size` is zero.
+    pub fn create_mapped(path: impl AsRef<Path>, block_size: usize) -> io::Result<Self> {
+        assert!(block_size > 0, "block size must be positive");
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)?;
+        let storage = Mapped {
+            file,
+            entry: Layout::new::<Entry<T>>(),
+            block_size,
+            blocks: Cell::new(0),
+        };
+        storage.file.set_len(BLOCK_ALIGN as u64)?;
+        storage.file.write_all_at(&storage.header().to_bytes(), 0)?;
+        Ok(Self::with_block_size_in(block_size, storage))
+    }
+
+    /// Reopens the pool of the file at `path`, whose values are at the same `PtrRepr`s as before.
+    ///
+    /// # Safety
+    /// The file must be written by a pool of the same `T` created by `create_mapped` of the same build of the program,
+    /// since the layout of `Entry<T>` may differ between builds. Only its size and alignment are checked.
+    /// `T` must not hold anything valid only in the run which wrote the file, such as references.
+    pub unsafe fn open_mapped(path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = OpenOptions::new().read(true).write(true).open(path)?;
+        let mut bytes = [0; Header::LEN];
+        file.read_exact_at(&mut bytes, 0)?;
+        let header = Header::from_bytes(&bytes)?;
+        if header.entry != Layout::new::<Entry<T>>() || header.block_size == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "file is not of a pool of this type",
+            ));
+        }
+        let storage = Mapped {
+            file,
+            entry: header.entry,
+            block_size: header.block_size,
+            blocks: Cell::new(header.blocks),
+        };
+        if storage.file.metadata()?.len() < storage.block_offset(header.blocks) as u64 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        let mut pool = Self::with_block_size_in(header.block_size, storage);
+        for k in 0..header.blocks {
+            let block = pool.storage.map(k)?;
+            pool.blocks.push(crate::storage::Block::new(block));
+        }
+        pool.len = pool
+            .blocks
+            .iter()
+            .flat_map(|block| block.iter())
+            .filter(|e| e.is_occupied())
+            .count();
+        pool.reindex_blocks();
+        // the head of the free list is not stored in the file
+        pool.rebuild_free_list();
+        Ok(pool)
+    }
+}
+
+#[test]
+fn test() {
+    use crate::PtrRepr;
+
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    struct Point {
+        x: f64,
+        y: f64,
+    }
+    let path = std::env::temp_dir().join(format!("mepoo-mapped-{}", std::process::id()));
+    let (reprs, freed) = {
+        let mut pool = Pool::create_mapped(&path, 100).unwrap();
+        let ptrs = (0..250)
+            .map(|i| {
+                pool.alloc(Point {
+                    x: i as f64,
+                    y: 0.0,
+                })
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(pool.iter_blocks().count(), 3);
+        for &p in ptrs.iter().step_by(2) {
+            pool.free(p);
+        }
+        pool[ptrs[1]].y = 1.0;
+        let reprs = ptrs
+            .iter()
+            .map(|&p| pool.ptr_to_repr(p).unwrap())
+            .collect::<Vec<_>>();
+        (reprs, pool.ptr_to_repr(ptrs[0]).unwrap())
+    };
+
+    let mut pool = unsafe { Pool::<Point, Mapped>::open_mapped(&path) }.unwrap();
+    assert_eq!(pool.len(), 125);
+    assert_eq!(pool.capacity(), 300);
+    pool.assert_consistent();
+    let get = |pool: &Pool<Point, Mapped>, repr: PtrRepr| {
+        pool.ptr_from_repr(repr)
+            .and_then(|p| pool.get(p))
+            .map(|r| *r)
+    };
+    assert_eq!(get(&pool, reprs[1]), Some(Point { x: 1.0, y: 1.0 }));
+    assert_eq!(get(&pool, reprs[249]), Some(Point { x: 249.0, y: 0.0 }));
+    assert_eq!(get(&pool, freed), None);
+    let p = pool.alloc(Point { x: -1.0, y: -1.0 });
+    assert_eq!(pool.ptr_to_repr(p).unwrap().generation, 1);
+    for _ in 0..200 {
+        pool.alloc(Point { x: 0.0, y: 0.0 });
+    }
+    assert_eq!(pool.iter_blocks().count(), 4);
+    drop(pool);
+
+    let pool = unsafe { Pool::<Point, Mapped>::open_mapped(&path) }.unwrap();
+    assert_eq!((pool.len(), pool.capacity()), (326, 400));
+    drop(pool);
+    assert!(unsafe { Pool::<u8, Mapped>::open_mapped(&path) }.is_err());
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn keep_block_order() {
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+
+    let path = std::env::temp_dir().join(format!("mepoo-mapped-order-{}", std::process::id()));
+    let reprs = {
+        let mut pool = Pool::create_mapped(&path, 4).unwrap();
+        let ptrs = (0..12).map(|i| pool.alloc(i)).collect::<Vec<_>>();
+        for &p in &ptrs[4..8] {
+            pool.free(p);
+        }
+        let swap = catch_unwind(AssertUnwindSafe(|| pool.swap_blocks(0, 1)));
+        let shrink = catch_unwind(AssertUnwindSafe(|| unsafe { pool.shrink_to_fit() }));
+        let compact = catch_unwind(AssertUnwindSafe(|| unsafe { pool.compact() }));
+        assert!(swap.is_err() && shrink.is_err() && compact.is_err());
+        assert_eq!(pool.iter_blocks().count(), 3);
+        pool.assert_consistent();
+        ptrs.iter()
+            .map(|&p| pool.ptr_to_repr(p).unwrap())
+            .collect::<Vec<_>>()
+    };
+
+    let pool = unsafe { Pool::<i32, Mapped>::open_mapped(&path) }.unwrap();
+    let values = reprs
+        .iter()
+        .map(|&repr| {
+            pool.ptr_from_repr(repr)
+                .and_then(|p| pool.get(p).map(|r| *r))
+        })
+        .collect::<Vec<_>>();
+    let expected = (0..12)
+        .map(|i| Some(i).filter(|i| !(4..8).contains(i)))
+        .collect::<Vec<_>>();
+    assert_eq!(values, expected);
+    drop(pool);
+    std::fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,304 @@
+use crate::{Pool, Ptr};
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::marker::PhantomData;
+use core::mem::MaybeUninit;
+
+/// The bytes of a slot of a size class, aligned enough for the values allocated by `MultiPool::alloc`.
+#[repr(C, align(16))]
+struct Bytes<const N: usize>([MaybeUninit<u8>; N]);
+
+/// A slot of a size class, which drops the value in it by `drop` if it holds a typed value.
+struct Slot<const N: usize> {
+    drop: Option<unsafe fn(*mut u8)>,
+    len: usize,
+    bytes: Bytes<N>,
+}
+
+impl<const N: usize> Drop for Slot<N> {
+    fn drop(&mut self) {
+        if let Some(drop) = self.drop {
+            unsafe { drop(self.bytes.0.as_mut_ptr().cast()) }
+        }
+    }
+}
+
+unsafe fn drop_value<T>(ptr: *mut u8) {
+    unsafe { core::ptr::drop_in_place(ptr.cast::<T>()) }
+}
+
+/// The pool of a size class, whose slots are of the same size.
+trait Class {
+    /// Allocates a slot of `len` bytes initialized with zeros, dropped by `drop`.
+    fn alloc(&mut self, len: usize, drop: Option<unsafe fn(*mut u8)>) -> Ptr<()>;
+    /// Returns the start and the length of the bytes of `p`.
+    fn bytes(&self, p: Ptr<()>) -> Option<(*const u8, usize)>;
+    fn bytes_mut(&mut self, p: Ptr<()>) -> Option<(*mut u8, usize)>;
+    /// Frees `p` without dropping its value.
+    fn forget(&mut self, p: Ptr<()>) -> bool;
+    fn free(&mut self, p: Ptr<()>) -> bool;
+    fn len(&self) -> usize;
+}
+
+impl<const N: usize> Class for Pool<Slot<N>> {
+    fn alloc(&mut self, len: usize, drop: Option<unsafe fn(*mut u8)>) -> Ptr<()> {
+        let slot = Slot {
+            drop,
+            len,
+            bytes: Bytes([MaybeUninit::new(0); N]),
+        };
+        Pool::alloc(self, slot).cast()
+    }
+    fn bytes(&self, p: Ptr<()>) -> Option<(*const u8, usize)> {
+        let slot = self.get(p.cast())?.get();
+        Some((slot.bytes.0.as_ptr().cast(), slot.len))
+    }
+    fn bytes_mut(&mut self, p: Ptr<()>) -> Option<(*mut u8, usize)> {
+        let slot = self.get_mut(p.cast())?;
+        Some((slot.bytes.0.as_mut_ptr().cast(), slot.len))
+    }
+    fn forget(&mut self, p: Ptr<()>) -> bool {
+        match self.get_mut(p.cast()) {
+            Some(slot) => slot.drop = None,
+            None => return false,
+        }
+        Pool::free(self, p.cast())
+    }
+    fn free(&mut self, p: Ptr<()>) -> bool {
+        Pool::free(self, p.cast())
+    }
+    fn len(&self) -> usize {
+        Pool::len(self)
+    }
+}
+
+/// The sizes of the classes of `MultiPool` in bytes.
+const CLASS_SIZES: [usize; 8] = [16, 32, 64, 128, 256, 512, 1024, 2048];
+
+/// A pointer to the bytes allocated by `MultiPool::alloc_bytes`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct BytesPtr {
+    class: usize,
+    ptr: Ptr<()>,
+}
+
+/// A pointer to a value allocated by `MultiPool::alloc`.
+pub struct MultiPtr<T> {
+    class: usize,
+    ptr: Ptr<()>,
+    _value: PhantomData<fn() -> T>,
+}
+
+/// A general allocator of small objects, which routes each allocation to the pool of the smallest size class
+/// which fits it, from 16 bytes to `MultiPool::MAX_SIZE` bytes by powers of two.
+/// Holding the byte strings and the values of different types within the same pools
+/// keeps the memory of the objects of different types reused by each other, e.g. for the heap of a scripting VM.
+pub struct MultiPool {
+    classes: Vec<Box<dyn Class>>,
+}
+
+impl MultiPool {
+    /// The largest size of an allocation in bytes.
+    pub const MAX_SIZE: usize = 2048;
+    /// The largest alignment of a value allocated by `alloc`.
+    pub const MAX_ALIGN: usize = 16;
+
+    pub fn new() -> Self {
+        fn class<const N: usize>() -> Box<dyn Class> {
+            // about 64 KiB per block
+            Box::new(Pool::<Slot<N>>::with_block_size((1 << 16) / N))
+        }
+        Self {
+            classes: alloc::vec![
+                class::<16>(),
+                class::<32>(),
+                class::<64>(),
+                class::<128>(),
+                class::<256>(),
+                class::<512>(),
+                class::<1024>(),
+                class::<2048>(),
+            ],
+        }
+    }
+
+    /// Returns the index of the size class of `len` bytes.
+    ///
+    /// # Panics
+    /// Panics if `len` exceeds `MAX_SIZE`.
+    fn class_of(len: usize) -> usize {
+        assert!(
+            len <= Self::MAX_SIZE,
+            "{} bytes exceeds the largest size class",
+            len
+        );
+        CLASS_SIZES.iter().position(|&size| len <= size).unwrap()
+    }
+
+    /// Number of the allocations of all classes.
+    pub fn len(&self) -> usize {
+        self.classes.iter().map(|class| class.len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Allocates `len` bytes initialized with zeros.
+    ///
+    /// # Panics
+    /// Panics if `len` exceeds `MAX_SIZE`.
+    pub fn alloc_bytes(&mut self, len: usize) -> BytesPtr {
+        let class = Self::class_of(len);
+        BytesPtr {
+            class,
+            ptr: self.classes[class].alloc(len, None),
+        }
+    }
+
+    /// Returns `None` if `p` is already freed.
+    pub fn get_bytes(&self, p: BytesPtr) -> Option<&[u8]> {
+        let (ptr, len) = self.classes[p.class].bytes(p.ptr)?;
+        Some(unsafe { core::slice::from_raw_parts(ptr, len) })
+    }
+
+    pub fn get_bytes_mut(&mut self, p: BytesPtr) -> Option<&mut [u8]> {
+        let (ptr, len) = self.classes[p.class].bytes_mut(p.ptr)?;
+        Some(unsafe { core::slice::from_raw_parts_mut(ptr, len) })
+    }
+
+    pub fn free_bytes(&mut self, p: BytesPtr) -> bool {
+        self.classes[p.class].free(p.ptr)
+    }
+
+    /// Allocates `value` in the size class of `T`, which is dropped when freed or when this pool is dropped.
+    /// Like `AnyPool`, `T` must be `'static`, since this pool does not borrow what its values borrow.
+    ///
+    /// ```compile_fail
+    /// let s = String::from("borrowed");
+    /// let mut pool = mepoo::MultiPool::new();
+    /// pool.alloc(&s);
+    /// ```
+    ///
+    /// # Panics
+    /// Panics if the size of `T` exceeds `MAX_SIZE`, or the alignment of `T` exceeds `MAX_ALIGN`.
+    pub fn alloc<T: 'static>(&mut self, value: T) -> MultiPtr<T> {
+        assert!(
+            core::mem::align_of::<T>() <= Self::MAX_ALIGN,
+            "alignment exceeds the one of the size classes"
+        );
+        let class = Self::class_of(core::mem::size_of::<T>());
+        let ptr = self.classes[class].alloc(core::mem::size_of::<T>(), Some(drop_value::<T>));
+        let (bytes, _) = self.classes[class].bytes_mut(ptr).unwrap();
+        unsafe { bytes.cast::<T>().write(value) };
+        MultiPtr {
+            class,
+            ptr,
+            _value: PhantomData,
+        }
+    }
+
+    /// Returns `None` if `p` is already freed.
+    pub fn get<T>(&self, p: MultiPtr<T>) -> Option<&T> {
+        let (bytes, _) = self.classes[p.class].bytes(p.ptr)?;
+        Some(unsafe { &*bytes.cast::<T>() })
+    }
+
+    pub fn get_mut<T>(&mut self, p: MultiPtr<T>) -> Option<&mut T> {
+        let (bytes, _) = self.classes[p.class].bytes_mut(p.ptr)?;
+        Some(unsafe { &mut *bytes.cast::<T>() })
+    }
+
+    /// Drops the value of `p`.
+    pub fn free<T>(&mut self, p: MultiPtr<T>) -> bool {
+        self.classes[p.class].free(p.ptr)
+    }
+
+    /// Moves the value of `p` out, or returns `None` if it is already freed.
+    pub fn take<T>(&mut self, p: MultiPtr<T>) -> Option<T> {
+        let (bytes, _) = self.classes[p.class].bytes_mut(p.ptr)?;
+        let value = unsafe { bytes.cast::<T>().read() };
+        self.classes[p.class].forget(p.ptr);
+        Some(value)
+    }
+}
+
+impl Default for MultiPool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl core::fmt::Debug for MultiPool {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(f, "MultiPool {{ len: {} }}", self.len())
+    }
+}
+
+impl<T> core::fmt::Debug for MultiPtr<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(f, "MultiPtr({}, {:?})", self.class, self.ptr)
+    }
+}
+impl<T> Clone for MultiPtr<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+impl<T> Copy for MultiPtr<T> {}
+impl<T> PartialEq for MultiPtr<T> {
+    fn eq(&self, rhs: &Self) -> bool {
+        self.ptr == rhs.ptr
+    }
+}
+impl<T> Eq for MultiPtr<T> {}
+impl<T> core::hash::Hash for MultiPtr<T> {
+    fn hash<H: core::hash::Hasher>(&self, state: &mut H) {
+        self.ptr.hash(state)
+    }
+}
+
+#[test]
+fn test() {
+    use alloc::rc::Rc;
+    use alloc::string::String;
+
+    assert_eq!(CLASS_SIZES.last(), Some(&MultiPool::MAX_SIZE));
+    let mut pool = MultiPool::new();
+    let a = pool.alloc_bytes(5);
+    let b = pool.alloc_bytes(100);
+    let empty = pool.alloc_bytes(0);
+    assert_eq!((a.class, b.class, empty.class), (0, 3, 0));
+    assert_eq!(pool.get_bytes(a), Some(&[0; 5][..]));
+    pool.get_bytes_mut(b).unwrap()[99] = 1;
+    assert_eq!(pool.get_bytes(b).unwrap()[98..], [0, 1]);
+    assert_eq!(pool.get_bytes(empty), Some(&[][..]));
+    assert!(pool.free_bytes(a) && !pool.free_bytes(a));
+    assert!(pool.get_bytes(a).is_none());
+
+    let rc = Rc::new(());
+    let s = pool.alloc(String::from("abc"));
+    let r = pool.alloc(rc.clone());
+    let big = pool.alloc([rc.clone(), rc.clone()]);
+    let aligned = pool.alloc(1u128 << 100);
+    assert_eq!(pool.len(), 6);
+    pool.get_mut(s).unwrap().push('d');
+    assert_eq!(pool.get(s).map(|s| s.as_str()), Some("abcd"));
+    assert_eq!(*pool.get(aligned).unwrap(), 1 << 100);
+    assert_eq!(Rc::strong_count(&rc), 4);
+    assert!(pool.free(r) && !pool.free(r));
+    assert_eq!(Rc::strong_count(&rc), 3);
+    let taken = pool.take(big).unwrap();
+    assert!(pool.get(big).is_none());
+    assert_eq!(Rc::strong_count(&rc), 3);
+    drop(taken);
+    pool.alloc(rc.clone());
+    drop(pool);
+    assert_eq!(Rc::strong_count(&rc), 1);
+}
+
+#[test]
+#[should_panic]
+fn too_large() {
+    MultiPool::new().alloc_bytes(MultiPool::MAX_SIZE + 1);
+}
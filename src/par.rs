@@ -0,0 +1,53 @@
+use crate::{Entry, Pool, Ptr};
+use rayon::prelude::*;
+
+impl<T: Send + Sync> Pool<T> {
+    /// Iterates over the occupied entries in parallel, splitting the work by block.
+    pub fn par_iter(&self) -> impl ParallelIterator<Item = (Ptr<T>, &T)> + '_ {
+        let id = self.id;
+        let blocks = self.iter_blocks().collect::<Vec<_>>();
+        blocks.into_par_iter().flat_map_iter(move |block| {
+            block.iter().filter_map(move |entry| match entry {
+                Entry::Occupied { value, .. } => Some((Ptr::to(entry, id), value)),
+                Entry::Vacant { .. } => None,
+            })
+        })
+    }
+
+    /// Iterates mutably over the occupied entries in parallel, splitting the work by block.
+    pub fn par_iter_mut(&mut self) -> impl ParallelIterator<Item = (Ptr<T>, &mut T)> + '_ {
+        let id = self.id;
+        let blocks = self
+            .blocks
+            .iter_mut()
+            .map(|block| &mut **block)
+            .collect::<Vec<_>>();
+        blocks.into_par_iter().flat_map_iter(move |block| {
+            block.iter_mut().filter_map(move |entry| {
+                let ptr = Ptr::to(entry, id);
+                match entry {
+                    Entry::Occupied { value, .. } => Some((ptr, value)),
+                    Entry::Vacant { .. } => None,
+                }
+            })
+        })
+    }
+}
+
+#[test]
+fn test() {
+    let mut pool = Pool::with_block_size(100);
+    let ptrs = (0..1000).map(|i| pool.alloc(i)).collect::<Vec<_>>();
+    for &p in ptrs.iter().step_by(3) {
+        pool.free(p);
+    }
+    pool.par_iter_mut().for_each(|(_, i)| *i *= 2);
+    assert_eq!(pool.par_iter().count(), pool.len());
+    assert!(pool
+        .par_iter()
+        .all(|(p, &i)| ptrs[i / 2] == p && i % 6 != 0));
+    assert_eq!(
+        pool.par_iter().map(|(_, &i)| i).sum::<usize>(),
+        pool.iter().map(|(_, &i)| i).sum::<usize>()
+    );
+}
@@ -0,0 +1,100 @@
+use crate::{BlockStorage, Heap, Pool, Ptr, Ref};
+use core::pin::Pin;
+
+/// A pool whose values are pinned: a value never moves until it is freed and dropped in place.
+///
+/// The entries of `Pool` never move by themselves, since a block is never reallocated,
+/// but some methods of `Pool` move the values out, such as `take` and `compact_vacant_to_front`.
+/// This exposes only the operations which do not move the values,
+/// so that futures and self-referential values can be pooled with `Pin`.
+#[derive(Debug)]
+pub struct PinnedPool<T, S: BlockStorage = Heap> {
+    pool: Pool<T, S>,
+}
+
+impl<T> PinnedPool<T> {
+    pub fn new() -> Self {
+        Self { pool: Pool::new() }
+    }
+}
+
+impl<T, S: BlockStorage> PinnedPool<T, S> {
+    pub fn from_pool(pool: Pool<T, S>) -> Self {
+        Self { pool }
+    }
+
+    pub fn pool(&self) -> &Pool<T, S> {
+        &self.pool
+    }
+
+    pub fn alloc(&mut self, value: T) -> Ptr<T> {
+        self.pool.alloc(value)
+    }
+
+    pub fn alloc_pinned(&mut self, value: T) -> (Ptr<T>, Pin<&mut T>) {
+        let ptr = self.pool.alloc(value);
+        (ptr, self.get_pin_mut(ptr).unwrap())
+    }
+
+    pub fn get(&self, p: Ptr<T>) -> Option<Ref<'_, T>> {
+        self.pool.get(p)
+    }
+
+    pub fn get_pin_mut(&mut self, p: Ptr<T>) -> Option<Pin<&mut T>> {
+        let value = self.pool.get_mut(p)?;
+        Some(unsafe { Pin::new_unchecked(value) })
+    }
+
+    /// Drops the value of `p` in place.
+    pub fn free(&mut self, p: Ptr<T>) -> bool {
+        self.pool.free(p)
+    }
+}
+
+impl<T> Default for PinnedPool<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[test]
+fn test() {
+    use core::marker::PhantomPinned;
+    use core::ptr::NonNull;
+
+    // a value which checks that it is not moved since it was pinned
+    struct SelfRef {
+        this: Option<NonNull<SelfRef>>,
+        _pin: PhantomPinned,
+    }
+    impl SelfRef {
+        fn init(self: Pin<&mut Self>) {
+            let this = unsafe { self.get_unchecked_mut() };
+            this.this = Some(NonNull::from(&*this));
+        }
+    }
+    impl Drop for SelfRef {
+        fn drop(&mut self) {
+            if let Some(this) = self.this {
+                assert_eq!(this.as_ptr() as *const _, self as *const _);
+            }
+        }
+    }
+
+    let mut pool = PinnedPool::new();
+    let ptrs = (0..10)
+        .map(|_| {
+            let (ptr, value) = pool.alloc_pinned(SelfRef {
+                this: None,
+                _pin: PhantomPinned,
+            });
+            value.init();
+            ptr
+        })
+        .collect::<Vec<_>>();
+    assert!(pool.free(ptrs[3]));
+    assert!(!pool.free(ptrs[3]));
+    assert_eq!(pool.pool().len(), 9);
+    pool.pool().assert_consistent();
+    assert!(pool.get_pin_mut(ptrs[5]).is_some());
+}
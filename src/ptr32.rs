@@ -0,0 +1,167 @@
+use crate::{BlockStorage, Pool, Ptr, PtrRepr, Ref};
+use core::convert::TryFrom;
+use core::marker::PhantomData;
+
+/// A compact pointer of 8 bytes, made of the position of the entry over all blocks and the generation.
+///
+/// Unlike `Ptr`, this does not know its pool, so using it with another pool is not detected.
+/// Since it refers to the entry by position, it is invalidated by `Pool::swap_blocks` and `Pool::shrink_to_fit`.
+/// The layout is guaranteed to be the index followed by the generation, as `to_bits` packs them.
+#[repr(C)]
+pub struct Ptr32<T> {
+    index: u32,
+    generation: u32,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T> Ptr32<T> {
+    pub(crate) fn new(index: u32, generation: u32) -> Self {
+        Self {
+            index,
+            generation,
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn index(self) -> u32 {
+        self.index
+    }
+
+    pub fn generation(self) -> u32 {
+        self.generation
+    }
+
+    /// Packs this into the index in the upper 32 bits and the generation in the lower 32 bits.
+    pub fn to_bits(self) -> u64 {
+        (self.index as u64) << 32 | self.generation as u64
+    }
+
+    pub fn from_bits(bits: u64) -> Self {
+        Self::new((bits >> 32) as u32, bits as u32)
+    }
+}
+
+impl<T, S: BlockStorage> Pool<T, S> {
+    /// Returns the compact pointer of `p`, or `None` if `p` belongs to another pool
+    /// or its position does not fit in `u32`.
+    pub fn to_ptr32(&self, p: Ptr<T>) -> Option<Ptr32<T>> {
+        let repr = self.ptr_to_repr(p)?;
+        Some(Ptr32 {
+            index: u32::try_from(self.starts[repr.block] + repr.slot).ok()?,
+            generation: repr.generation,
+            _marker: PhantomData,
+        })
+    }
+
+    /// Returns the pointer of `p`, or `None` if the position of `p` is out of this pool.
+    pub fn from_ptr32(&self, p: Ptr32<T>) -> Option<Ptr<T>> {
+        let (block, slot) = self.locate_index(p.index as usize)?;
+        self.ptr_from_repr(PtrRepr {
+            block,
+            slot,
+            generation: p.generation,
+        })
+    }
+
+    /// # Panics
+    /// Panics if the position of the allocated entry does not fit in `u32`.
+    pub fn alloc32(&mut self, value: T) -> Ptr32<T> {
+        let p = self.alloc(value);
+        self.to_ptr32(p).expect("pool is too large for Ptr32")
+    }
+
+    pub fn get32(&self, p: Ptr32<T>) -> Option<Ref<'_, T>> {
+        self.get(self.from_ptr32(p)?)
+    }
+
+    pub fn get32_mut(&mut self, p: Ptr32<T>) -> Option<&mut T> {
+        let p = self.from_ptr32(p)?;
+        self.get_mut(p)
+    }
+
+    pub fn free32(&mut self, p: Ptr32<T>) -> bool {
+        match self.from_ptr32(p) {
+            Some(p) => self.free(p),
+            None => false,
+        }
+    }
+}
+
+impl<T> core::fmt::Debug for Ptr32<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(
+            f,
+            "Ptr32 {{ index: {}, generation: {} }}",
+            self.index, self.generation
+        )
+    }
+}
+impl<T> Clone for Ptr32<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+impl<T> Copy for Ptr32<T> {}
+impl<T> PartialEq for Ptr32<T> {
+    fn eq(&self, rhs: &Self) -> bool {
+        (self.index, self.generation) == (rhs.index, rhs.generation)
+    }
+}
+impl<T> Eq for Ptr32<T> {}
+impl<T> core::hash::Hash for Ptr32<T> {
+    fn hash<H: core::hash::Hasher>(&self, state: &mut H) {
+        (self.index, self.generation).hash(state)
+    }
+}
+impl<T> PartialOrd for Ptr32<T> {
+    fn partial_cmp(&self, rhs: &Self) -> Option<core::cmp::Ordering> {
+        Some(self.cmp(rhs))
+    }
+}
+impl<T> Ord for Ptr32<T> {
+    fn cmp(&self, rhs: &Self) -> core::cmp::Ordering {
+        (self.index, self.generation).cmp(&(rhs.index, rhs.generation))
+    }
+}
+
+#[test]
+fn test() {
+    use crate::{GrowthPolicy, PoolBuilder};
+    use alloc::vec::Vec;
+
+    assert_eq!(core::mem::size_of::<Ptr32<u64>>(), 8);
+    let mut pool = PoolBuilder::new()
+        .block_size(3)
+        .growth(GrowthPolicy::Doubling)
+        .build();
+    let ptrs = (0..20).map(|i| pool.alloc32(i)).collect::<Vec<_>>();
+    let mut indices = ptrs.iter().map(|p| p.index()).collect::<Vec<_>>();
+    indices.sort_unstable();
+    indices.dedup();
+    assert_eq!(indices.len(), 20);
+    assert!(indices.iter().all(|&i| (i as usize) < pool.capacity()));
+    assert!(ptrs
+        .iter()
+        .zip(0..)
+        .all(|(&p, i)| pool.get32(p).unwrap().get() == &i));
+    for &p in &ptrs {
+        assert_eq!(pool.to_ptr32(pool.from_ptr32(p).unwrap()), Some(p));
+    }
+    *pool.get32_mut(ptrs[4]).unwrap() = 40;
+    assert_eq!(pool[pool.from_ptr32(ptrs[4]).unwrap()], 40);
+    assert!(pool.free32(ptrs[4]));
+    assert!(!pool.free32(ptrs[4]));
+    assert!(pool.get32(ptrs[4]).is_none());
+    let p = pool.alloc32(100);
+    assert_eq!((p.index(), p.generation()), (ptrs[4].index(), 1));
+    assert!(pool
+        .from_ptr32(Ptr32 {
+            index: 21,
+            generation: 0,
+            _marker: PhantomData
+        })
+        .is_none());
+    assert_eq!(pool.to_ptr32(Pool::new().alloc(0)), None);
+    assert_eq!(Ptr32::from_bits(p.to_bits()), p);
+    assert_eq!(p.to_bits(), (p.index() as u64) << 32 | 1);
+}
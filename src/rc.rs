@@ -0,0 +1,123 @@
+use crate::{Pool, Ptr};
+use core::cell::{Cell, UnsafeCell};
+use core::ops::Deref;
+
+struct RcBox<T> {
+    count: Cell<usize>,
+    value: T,
+}
+
+/// A pool of reference-counted values.
+/// The entry of a value is freed when the last `PoolRc` to it is dropped.
+pub struct RcPool<T> {
+    pool: UnsafeCell<Pool<RcBox<T>>>,
+}
+
+impl<T> RcPool<T> {
+    pub fn new() -> Self {
+        Self {
+            pool: UnsafeCell::new(Pool::new()),
+        }
+    }
+
+    /// Number of values alive in this pool.
+    pub fn len(&self) -> usize {
+        unsafe { (*self.pool.get()).len() }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn alloc(&self, value: T) -> PoolRc<'_, T> {
+        let value = RcBox {
+            count: Cell::new(1),
+            value,
+        };
+        // `Pool::alloc` never moves the entries, and writes only the vacant entry through a raw pointer to it,
+        // so the `&mut Pool` covers none of the values borrowed by other `PoolRc`s.
+        let ptr = unsafe { (*self.pool.get()).alloc(value) };
+        PoolRc { pool: self, ptr }
+    }
+}
+
+impl<T> Default for RcPool<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A shared handle to a value in `RcPool`, like `Rc`.
+pub struct PoolRc<'a, T> {
+    pool: &'a RcPool<T>,
+    ptr: Ptr<RcBox<T>>,
+}
+
+impl<'a, T> PoolRc<'a, T> {
+    fn rc_box(&self) -> &RcBox<T> {
+        unsafe { self.ptr.as_ref() }.unwrap().get()
+    }
+
+    pub fn strong_count(this: &Self) -> usize {
+        this.rc_box().count.get()
+    }
+
+    pub fn ptr_eq(this: &Self, other: &Self) -> bool {
+        this.ptr == other.ptr
+    }
+}
+
+impl<'a, T> Clone for PoolRc<'a, T> {
+    fn clone(&self) -> Self {
+        let count = &self.rc_box().count;
+        count.set(count.get() + 1);
+        Self {
+            pool: self.pool,
+            ptr: self.ptr,
+        }
+    }
+}
+
+impl<'a, T> Drop for PoolRc<'a, T> {
+    fn drop(&mut self) {
+        let count = &self.rc_box().count;
+        count.set(count.get() - 1);
+        if count.get() == 0 {
+            // as in `RcPool::alloc`, `Pool::take` writes only the entry of this value through a raw pointer to it
+            let value = unsafe { (*self.pool.pool.get()).take(self.ptr) };
+            // dropped after the pool is released, since dropping `T` may run arbitrary code
+            drop(value);
+        }
+    }
+}
+
+impl<'a, T> Deref for PoolRc<'a, T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        &self.rc_box().value
+    }
+}
+
+impl<'a, T: core::fmt::Debug> core::fmt::Debug for PoolRc<'a, T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+#[test]
+fn test() {
+    let pool = RcPool::new();
+    let a = pool.alloc(String::from("a"));
+    let b = a.clone();
+    assert_eq!(PoolRc::strong_count(&a), 2);
+    assert!(PoolRc::ptr_eq(&a, &b));
+    let c = pool.alloc(String::from("c"));
+    assert!(!PoolRc::ptr_eq(&a, &c));
+    assert_eq!(pool.len(), 2);
+    drop(a);
+    assert_eq!(*b, "a");
+    assert_eq!(pool.len(), 2);
+    drop(b);
+    assert_eq!(pool.len(), 1);
+    assert_eq!(*c, "c");
+}
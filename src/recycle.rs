@@ -0,0 +1,131 @@
+use crate::{BlockStorage, Heap, Pool, Ptr, Ref};
+use alloc::string::String;
+use alloc::vec::Vec;
+
+/// A value which can be reset to its initial state, keeping the resources it owns such as the capacity of a buffer.
+pub trait Reset {
+    fn reset(&mut self);
+}
+
+impl<T> Reset for Vec<T> {
+    fn reset(&mut self) {
+        self.clear();
+    }
+}
+
+impl Reset for String {
+    fn reset(&mut self) {
+        self.clear();
+    }
+}
+
+/// A pool which keeps the recycled values to reuse them for later allocations,
+/// instead of dropping them and constructing new ones, like a classic object pool.
+#[derive(Debug)]
+pub struct RecyclingPool<T, S: BlockStorage = Heap> {
+    pool: Pool<T, S>,
+    spare: Vec<T>,
+}
+
+impl<T> RecyclingPool<T> {
+    pub fn new() -> Self {
+        Self::from_pool(Pool::new())
+    }
+}
+
+impl<T, S: BlockStorage> RecyclingPool<T, S> {
+    pub fn from_pool(pool: Pool<T, S>) -> Self {
+        Self {
+            pool,
+            spare: Vec::new(),
+        }
+    }
+
+    pub fn pool(&self) -> &Pool<T, S> {
+        &self.pool
+    }
+
+    pub fn pool_mut(&mut self) -> &mut Pool<T, S> {
+        &mut self.pool
+    }
+
+    /// Number of the recycled values waiting to be reused.
+    pub fn spare_len(&self) -> usize {
+        self.spare.len()
+    }
+
+    pub fn alloc(&mut self, value: T) -> Ptr<T> {
+        self.pool.alloc(value)
+    }
+
+    pub fn get(&self, p: Ptr<T>) -> Option<Ref<'_, T>> {
+        self.pool.get(p)
+    }
+
+    pub fn get_mut(&mut self, p: Ptr<T>) -> Option<&mut T> {
+        self.pool.get_mut(p)
+    }
+
+    /// Drops the value of `p` without recycling it.
+    pub fn free(&mut self, p: Ptr<T>) -> bool {
+        self.pool.free(p)
+    }
+
+    /// Drops the recycled values.
+    pub fn clear_spare(&mut self) {
+        self.spare.clear();
+    }
+}
+
+impl<T: Reset, S: BlockStorage> RecyclingPool<T, S> {
+    /// Frees `p`, keeping its value reset for a later `alloc_recycled`.
+    pub fn recycle(&mut self, p: Ptr<T>) -> bool {
+        match self.pool.take(p) {
+            Some(mut value) => {
+                value.reset();
+                self.spare.push(value);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Allocates a recycled value, or a default value if none is recycled, after it is initialized by `init`.
+    pub fn alloc_recycled(&mut self, init: impl FnOnce(&mut T)) -> Ptr<T>
+    where
+        T: Default,
+    {
+        let mut value = self.spare.pop().unwrap_or_default();
+        init(&mut value);
+        self.pool.alloc(value)
+    }
+}
+
+impl<T> Default for RecyclingPool<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[test]
+fn test() {
+    let mut pool = RecyclingPool::<Vec<u8>>::new();
+    let a = pool.alloc_recycled(|buf| buf.extend_from_slice(b"hello"));
+    assert_eq!(pool.get(a).unwrap().as_slice(), b"hello");
+    let capacity = pool.get(a).unwrap().capacity();
+    assert!(pool.recycle(a));
+    assert!(!pool.recycle(a));
+    assert_eq!((pool.spare_len(), pool.pool().len()), (1, 0));
+
+    let b = pool.alloc_recycled(|buf| assert!(buf.is_empty()));
+    assert_eq!(pool.get(b).unwrap().capacity(), capacity);
+    assert_eq!(pool.spare_len(), 0);
+    pool.get_mut(b).unwrap().push(1);
+    assert!(pool.free(b));
+    assert_eq!(pool.spare_len(), 0);
+
+    let c = pool.alloc(vec![1, 2, 3]);
+    pool.recycle(c);
+    pool.clear_spare();
+    assert_eq!(pool.spare_len(), 0);
+}
@@ -0,0 +1,95 @@
+use crate::{BlockStorage, Pool, PoolId, Ptr};
+use std::sync::{Arc, Mutex};
+
+pub(crate) type Queue = Arc<Mutex<Vec<Ptr<()>>>>;
+
+/// A handle by which other threads schedule freeing values of a `Pool`,
+/// which the owner of the pool applies by `Pool::collect_remote_frees`.
+pub struct RemoteFree<T> {
+    queue: Queue,
+    pool_id: PoolId,
+    _marker: core::marker::PhantomData<fn(Ptr<T>)>,
+}
+
+impl<T> RemoteFree<T> {
+    /// Schedules freeing `p`.
+    ///
+    /// # Panics
+    /// Panics if `p` does not belong to the pool of this handle.
+    pub fn free(&self, p: Ptr<T>) {
+        assert!(p.pool_id == self.pool_id);
+        self.queue.lock().unwrap().push(p.cast());
+    }
+}
+
+impl<T, S: BlockStorage> Pool<T, S> {
+    /// Returns a handle to schedule freeing values of this pool from other threads.
+    pub fn remote_free(&mut self) -> RemoteFree<T> {
+        RemoteFree {
+            queue: self.remote.get_or_insert_with(Default::default).clone(),
+            pool_id: self.id,
+            _marker: core::marker::PhantomData,
+        }
+    }
+
+    /// Frees the values scheduled by `RemoteFree`, and returns how many values are freed.
+    /// A pointer already freed is ignored.
+    pub fn collect_remote_frees(&mut self) -> usize {
+        let ptrs = match &self.remote {
+            Some(queue) => core::mem::take(&mut *queue.lock().unwrap()),
+            None => return 0,
+        };
+        ptrs.into_iter()
+            .filter(|&p| self.drop_entry(p.cast()))
+            .count()
+    }
+}
+
+impl<T> Clone for RemoteFree<T> {
+    fn clone(&self) -> Self {
+        Self {
+            queue: self.queue.clone(),
+            pool_id: self.pool_id,
+            _marker: core::marker::PhantomData,
+        }
+    }
+}
+
+impl<T> core::fmt::Debug for RemoteFree<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(f, "RemoteFree({:?})", self.pool_id)
+    }
+}
+
+#[test]
+fn test() {
+    let mut pool = Pool::new();
+    let ptrs = (0..100).map(|i| pool.alloc(i)).collect::<Vec<_>>();
+    assert_eq!(pool.collect_remote_frees(), 0);
+    let remote = pool.remote_free();
+    std::thread::scope(|scope| {
+        for chunk in ptrs.chunks(25) {
+            let remote = remote.clone();
+            scope.spawn(move || {
+                for &p in chunk {
+                    remote.free(p);
+                }
+                // freed twice, which is ignored
+                remote.free(chunk[0]);
+            });
+        }
+    });
+    assert_eq!(pool.len(), 100);
+    assert_eq!(pool.collect_remote_frees(), 100);
+    assert!(pool.is_empty());
+    pool.assert_consistent();
+    assert_eq!(pool.collect_remote_frees(), 0);
+}
+
+#[test]
+#[should_panic]
+fn wrong_pool() {
+    let mut pool = Pool::new();
+    let mut other = Pool::new();
+    other.remote_free().free(pool.alloc(0));
+}
@@ -0,0 +1,123 @@
+use crate::{BlockStorage, Entry, Pool, Ptr};
+use core::ptr::NonNull;
+use std::collections::HashMap;
+
+/// A map which associates extra data with the entries of `Pool`, keyed by `Ptr`.
+/// A value is keyed by the slot and the generation of the entry,
+/// so that it is not found anymore once the entry is freed, even if the slot is allocated again.
+#[derive(Debug)]
+pub struct SecondaryMap<T, V> {
+    map: HashMap<NonNull<Entry<T>>, (Ptr<T>, V)>,
+}
+
+impl<T, V> SecondaryMap<T, V> {
+    pub fn new() -> Self {
+        Self {
+            map: HashMap::new(),
+        }
+    }
+
+    /// Number of values, including the ones for freed entries which are not removed yet.
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    /// Inserts `value` for `p`, and returns the old value for `p` if any.
+    /// A value for an older generation of the slot is replaced silently,
+    /// and nothing is inserted if `p` is older than the value already inserted for the slot.
+    pub fn insert(&mut self, p: Ptr<T>, value: V) -> Option<V> {
+        match self.map.get_mut(&p.ptr) {
+            Some((q, old)) if q.generation == p.generation && q.pool_id == p.pool_id => {
+                Some(core::mem::replace(old, value))
+            }
+            Some((q, _)) if q.generation > p.generation && q.pool_id == p.pool_id => None,
+            _ => {
+                self.map.insert(p.ptr, (p, value));
+                None
+            }
+        }
+    }
+
+    pub fn remove(&mut self, p: Ptr<T>) -> Option<V> {
+        if self.contains_key(p) {
+            self.map.remove(&p.ptr).map(|(_, value)| value)
+        } else {
+            None
+        }
+    }
+
+    pub fn contains_key(&self, p: Ptr<T>) -> bool {
+        self.get(p).is_some()
+    }
+
+    pub fn get(&self, p: Ptr<T>) -> Option<&V> {
+        match self.map.get(&p.ptr) {
+            Some((q, value)) if *q == p => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn get_mut(&mut self, p: Ptr<T>) -> Option<&mut V> {
+        match self.map.get_mut(&p.ptr) {
+            Some((q, value)) if *q == p => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Iterates over the values in arbitrary order, including the ones for freed entries.
+    pub fn iter(&self) -> impl Iterator<Item = (Ptr<T>, &V)> {
+        self.map.values().map(|(p, value)| (*p, value))
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (Ptr<T>, &mut V)> {
+        self.map.values_mut().map(|(p, value)| (*p, value))
+    }
+
+    /// Removes the values for the entries already freed in `pool`, or for the entries of other pools.
+    pub fn retain_alive<S: BlockStorage>(&mut self, pool: &Pool<T, S>) {
+        self.map
+            .retain(|_, (p, _)| p.pool_id == pool.id() && pool.get(*p).is_some());
+    }
+}
+
+impl<T, V> Default for SecondaryMap<T, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[test]
+fn test() {
+    let mut pool = Pool::new();
+    let mut map = SecondaryMap::new();
+    let a = pool.alloc("a");
+    let b = pool.alloc("b");
+    assert_eq!(map.insert(a, 1), None);
+    assert_eq!(map.insert(b, 2), None);
+    assert_eq!(map.insert(a, 10), Some(1));
+    assert_eq!(map.get(a), Some(&10));
+    *map.get_mut(b).unwrap() += 1;
+    assert_eq!(map.iter().map(|(_, &v)| v).sum::<i32>(), 13);
+
+    pool.free(a);
+    let c = pool.alloc("c");
+    assert_eq!(c.ptr, a.ptr);
+    assert_eq!(map.get(a), Some(&10));
+    assert_eq!(map.get(c), None);
+    assert_eq!(map.insert(c, 100), None);
+    assert_eq!(map.get(a), None);
+    assert_eq!(map.insert(a, 1000), None);
+    assert_eq!(map.get(c), Some(&100));
+
+    pool.free(b);
+    assert_eq!(map.len(), 2);
+    map.retain_alive(&pool);
+    assert_eq!(map.len(), 1);
+    assert!(!map.contains_key(b));
+    assert_eq!(map.remove(c), Some(100));
+    assert!(map.is_empty());
+}
@@ -0,0 +1,399 @@
+//! `Pool` is serialized with the generations of all entries, and `Ptr` by `PtrRepr`.
+//! A `Ptr` can be serialized only as a part of the value of its pool,
+//! and is deserialized into a pointer to the pool being deserialized.
+use crate::{Entry, Pool, PoolId, Ptr, PtrRepr};
+use serde::de::{self, DeserializeSeed, IgnoredAny, MapAccess, SeqAccess, Visitor};
+use serde::ser::{self, SerializeStruct};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::cell::RefCell;
+use std::convert::TryFrom;
+use std::marker::PhantomData;
+use std::ptr::NonNull;
+
+/// The blocks of a pool being serialized or deserialized, as pairs of the first entry and the length.
+struct Context {
+    pool_id: PoolId,
+    blocks: Vec<(NonNull<u8>, usize)>,
+}
+
+thread_local! {
+    static CONTEXTS: RefCell<Vec<Context>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Makes the pointers to `pool` serializable until dropped.
+struct Scope;
+
+impl Scope {
+    fn enter<T>(pool: &Pool<T>) -> Self {
+        let blocks = pool
+            .iter_blocks()
+            .map(|block| (NonNull::from(&block[0]).cast(), block.len()))
+            .collect();
+        Self::push(pool.id(), blocks)
+    }
+
+    fn enter_pending<T>(pending: &Pending<T>) -> Self {
+        let blocks = pending
+            .blocks
+            .iter()
+            .map(|block| {
+                (
+                    NonNull::new(block.as_ptr() as *mut u8).unwrap(),
+                    block.capacity(),
+                )
+            })
+            .collect();
+        Self::push(pending.pool.id(), blocks)
+    }
+
+    fn push(pool_id: PoolId, blocks: Vec<(NonNull<u8>, usize)>) -> Self {
+        CONTEXTS.with(|contexts| contexts.borrow_mut().push(Context { pool_id, blocks }));
+        Scope
+    }
+}
+
+impl Drop for Scope {
+    fn drop(&mut self) {
+        CONTEXTS.with(|contexts| contexts.borrow_mut().pop());
+    }
+}
+
+impl Context {
+    fn repr<T>(&self, p: Ptr<T>) -> Option<PtrRepr> {
+        let size = std::mem::size_of::<Entry<T>>();
+        let addr = p.ptr.as_ptr() as usize;
+        self.blocks
+            .iter()
+            .enumerate()
+            .find_map(|(block, &(first, len))| {
+                let offset = addr.checked_sub(first.as_ptr() as usize)?;
+                (offset < len * size).then(|| PtrRepr {
+                    block,
+                    slot: offset / size,
+                    generation: p.generation,
+                })
+            })
+    }
+
+    fn resolve<T>(&self, repr: PtrRepr) -> Option<Ptr<T>> {
+        let &(first, len) = self.blocks.get(repr.block)?;
+        if repr.slot >= len {
+            return None;
+        }
+        let ptr = unsafe { first.cast::<Entry<T>>().as_ptr().add(repr.slot) };
+        Some(Ptr {
+            ptr: NonNull::new(ptr)?,
+            pool_id: self.pool_id,
+            generation: repr.generation,
+        })
+    }
+}
+
+impl<T> Serialize for Ptr<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let repr = CONTEXTS.with(|contexts| {
+            let contexts = contexts.borrow();
+            let context = contexts.iter().rev().find(|c| c.pool_id == self.pool_id)?;
+            context.repr(*self)
+        });
+        match repr {
+            Some(repr) => repr.serialize(serializer),
+            None => Err(ser::Error::custom(
+                "Ptr can be serialized only as a part of its pool",
+            )),
+        }
+    }
+}
+
+impl<'de, T> Deserialize<'de> for Ptr<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let repr = PtrRepr::deserialize(deserializer)?;
+        CONTEXTS
+            .with(|contexts| contexts.borrow().last().map(|c| c.resolve(repr)))
+            .ok_or_else(|| de::Error::custom("Ptr can be deserialized only as a part of a pool"))?
+            .ok_or_else(|| de::Error::custom(format!("{:?} is out of the pool", repr)))
+    }
+}
+
+struct Entries<'a, T>(&'a Pool<T>);
+
+impl<'a, T: Serialize> Serialize for Entries<'a, T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(
+            self.0
+                .iter_blocks()
+                .flat_map(|block| block.iter())
+                .map(|entry| match entry {
+                    Entry::Occupied { value, generation } => (*generation, Some(value)),
+                    Entry::Vacant { generation, .. } => (*generation, None),
+                }),
+        )
+    }
+}
+
+impl<T: Serialize> Serialize for Pool<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let _scope = Scope::enter(self);
+        let block_lens = self
+            .iter_blocks()
+            .map(|block| block.len())
+            .collect::<Vec<_>>();
+        let mut state = serializer.serialize_struct("Pool", 3)?;
+        state.serialize_field("block_size", &self.block_size)?;
+        state.serialize_field("block_lens", &block_lens)?;
+        state.serialize_field("entries", &Entries(self))?;
+        state.end()
+    }
+}
+
+/// A pool being deserialized, whose blocks are reserved but filled only as the entries are read,
+/// so that untrusted block lengths cost no more memory than the entries actually given.
+/// The blocks never reallocate, so the pointers to them can be resolved before they are filled.
+struct Pending<T> {
+    pool: Pool<T>,
+    blocks: Vec<Vec<Entry<T>>>,
+}
+
+impl<T> Pending<T> {
+    fn capacity(&self) -> usize {
+        self.blocks.iter().map(|block| block.capacity()).sum()
+    }
+}
+
+/// Fills the reserved blocks with the entries.
+struct EntriesSeed<'a, T>(&'a mut Pending<T>);
+
+impl<'de, 'a, T: Deserialize<'de>> DeserializeSeed<'de> for EntriesSeed<'a, T> {
+    type Value = ();
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+impl<'de, 'a, T: Deserialize<'de>> Visitor<'de> for EntriesSeed<'a, T> {
+    type Value = ();
+
+    fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{} entries", self.0.capacity())
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<(), A::Error> {
+        let capacity = self.0.capacity();
+        if let Some(len) = seq.size_hint().filter(|&len| len != capacity) {
+            return Err(de::Error::invalid_length(len, &self));
+        }
+        let mut i = 0;
+        for block in &mut self.0.blocks {
+            block.clear();
+            while block.len() < block.capacity() {
+                let (generation, value) =
+                    seq.next_element::<(u32, Option<T>)>()?.ok_or_else(|| {
+                        de::Error::invalid_length(i, &format!("{}", capacity).as_str())
+                    })?;
+                block.push(match value {
+                    Some(value) => Entry::Occupied { value, generation },
+                    None => Entry::Vacant {
+                        next: None,
+                        generation,
+                    },
+                });
+                i += 1;
+            }
+        }
+        if seq.next_element::<IgnoredAny>()?.is_some() {
+            return Err(de::Error::custom(format!("more than {} entries", capacity)));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(field_identifier, rename_all = "snake_case")]
+enum Field {
+    BlockSize,
+    BlockLens,
+    Entries,
+}
+
+struct PoolVisitor<T>(PhantomData<T>);
+
+impl<T> PoolVisitor<T> {
+    /// Reserves the blocks of `block_lens`, which are checked first since they are read from the untrusted input.
+    /// The reserved memory is not touched until the entries are read.
+    fn with_blocks<E: de::Error>(block_lens: Vec<usize>) -> Result<Pending<T>, E> {
+        if u32::try_from(block_lens.len()).is_err() {
+            return Err(E::custom(format!(
+                "{} blocks are too many",
+                block_lens.len()
+            )));
+        }
+        let entry_size = std::mem::size_of::<Entry<T>>();
+        for &len in &block_lens {
+            if len == 0 {
+                return Err(E::custom("empty block"));
+            }
+            let bytes = len.checked_mul(entry_size);
+            if u32::try_from(len).is_err() || bytes.is_none_or(|bytes| bytes > isize::MAX as usize)
+            {
+                return Err(E::custom(format!("block of {} entries is too large", len)));
+            }
+        }
+        let mut blocks = Vec::with_capacity(block_lens.len());
+        for len in block_lens {
+            let mut block = Vec::new();
+            block
+                .try_reserve_exact(len)
+                .map_err(|_| E::custom(format!("failed to allocate a block of {} entries", len)))?;
+            blocks.push(block);
+        }
+        Ok(Pending {
+            pool: Pool::new(),
+            blocks,
+        })
+    }
+
+    fn finish<E: de::Error>(pending: Pending<T>, block_size: usize) -> Result<Pool<T>, E> {
+        if block_size == 0 {
+            return Err(E::invalid_value(
+                de::Unexpected::Unsigned(0),
+                &"a positive block size",
+            ));
+        }
+        let Pending { mut pool, blocks } = pending;
+        // the blocks are full, so they are boxed without reallocation
+        pool.blocks.extend(
+            blocks
+                .into_iter()
+                .map(|block| crate::storage::Block::new(block.into_boxed_slice())),
+        );
+        pool.block_size = block_size;
+        pool.reindex_blocks();
+        pool.len = pool
+            .iter_blocks()
+            .flatten()
+            .filter(|e| e.is_occupied())
+            .count();
+        pool.rebuild_free_list();
+        Ok(pool)
+    }
+}
+
+impl<'de, T: Deserialize<'de>> Visitor<'de> for PoolVisitor<T> {
+    type Value = Pool<T>;
+
+    fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str("struct Pool")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Pool<T>, A::Error> {
+        let block_size = seq
+            .next_element()?
+            .ok_or_else(|| de::Error::invalid_length(0, &self))?;
+        let block_lens = seq
+            .next_element()?
+            .ok_or_else(|| de::Error::invalid_length(1, &self))?;
+        let mut pending = Self::with_blocks(block_lens)?;
+        let scope = Scope::enter_pending(&pending);
+        seq.next_element_seed(EntriesSeed(&mut pending))?
+            .ok_or_else(|| de::Error::invalid_length(2, &self))?;
+        drop(scope);
+        Self::finish(pending, block_size)
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Pool<T>, A::Error> {
+        let mut block_size = None;
+        let mut pending = None;
+        let mut filled = false;
+        while let Some(field) = map.next_key()? {
+            match field {
+                Field::BlockSize => block_size = Some(map.next_value()?),
+                Field::BlockLens => pending = Some(Self::with_blocks(map.next_value()?)?),
+                Field::Entries => {
+                    let pending = pending
+                        .as_mut()
+                        .ok_or_else(|| de::Error::custom("`block_lens` must precede `entries`"))?;
+                    let _scope = Scope::enter_pending(pending);
+                    map.next_value_seed(EntriesSeed(pending))?;
+                    filled = true;
+                }
+            }
+        }
+        let block_size = block_size.ok_or_else(|| de::Error::missing_field("block_size"))?;
+        let pending = pending.ok_or_else(|| de::Error::missing_field("block_lens"))?;
+        if !filled {
+            return Err(de::Error::missing_field("entries"));
+        }
+        Self::finish(pending, block_size)
+    }
+}
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for Pool<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        const FIELDS: &[&str] = &["block_size", "block_lens", "entries"];
+        deserializer.deserialize_struct("Pool", FIELDS, PoolVisitor(PhantomData))
+    }
+}
+
+#[test]
+fn test() {
+    #[derive(Serialize, Deserialize)]
+    struct Node {
+        value: i32,
+        next: Option<Ptr<Node>>,
+    }
+    let mut pool = Pool::with_block_size(2);
+    let mut head = None;
+    for value in 0..5 {
+        head = Some(pool.alloc(Node { value, next: head }));
+    }
+    let stale = pool.alloc(Node {
+        value: 5,
+        next: None,
+    });
+    pool.free(stale);
+    let stale = pool.ptr_to_repr(stale).unwrap();
+    let head = pool.ptr_to_repr(head.unwrap()).unwrap();
+
+    let json = serde_json::to_string(&pool).unwrap();
+    let pool: Pool<Node> = serde_json::from_str(&json).unwrap();
+    pool.assert_consistent();
+    assert_eq!(pool.len(), 5);
+    assert_eq!(pool.block_size(), 2);
+    let mut values = Vec::new();
+    let mut p = pool.ptr_from_repr(head);
+    while let Some(node) = p.and_then(|p| pool.get(p)) {
+        values.push(node.get().value);
+        p = node.next;
+    }
+    assert_eq!(values, vec![4, 3, 2, 1, 0]);
+    assert!(pool.get(pool.ptr_from_repr(stale).unwrap()).is_none());
+
+    assert!(serde_json::to_string(&Ptr::<i32>::DANGLING).is_err());
+    assert!(serde_json::from_str::<Pool<i32>>(
+        r#"{"block_size":2,"block_lens":[2],"entries":[[0,null]]}"#
+    )
+    .is_err());
+}
+
+#[test]
+fn malformed() {
+    let error = |json: &str| {
+        serde_json::from_str::<Pool<i32>>(json)
+            .err()
+            .unwrap()
+            .to_string()
+    };
+    assert!(
+        error(r#"{"block_size":2,"block_lens":[4294967296],"entries":[]}"#)
+            .starts_with("block of 4294967296 entries is too large")
+    );
+    assert!(error(r#"{"block_size":2,"block_lens":[0],"entries":[]}"#).starts_with("empty block"));
+    assert!(
+        error(r#"{"block_size":2,"block_lens":[1,2],"entries":[[0,1]]}"#)
+            .starts_with("invalid length 1")
+    );
+    assert!(
+        error(r#"{"block_size":2,"block_lens":[200000000],"entries":[]}"#)
+            .starts_with("invalid length 0")
+    );
+}
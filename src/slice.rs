@@ -0,0 +1,358 @@
+use crate::{Pool, Ptr};
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::marker::PhantomData;
+use core::mem::MaybeUninit;
+
+/// The range of the elements of a slice in a block of `SlicePool`.
+#[derive(Debug)]
+struct Extent {
+    block: usize,
+    start: usize,
+    len: usize,
+}
+
+/// A pointer to a slice in `SlicePool`.
+pub struct SlicePtr<T> {
+    ptr: Ptr<Extent>,
+    _elem: PhantomData<fn(&T)>,
+}
+
+/// A pool of slices of different lengths, which are placed in the vacant ranges of shared blocks
+/// instead of being boxed one by one, and freed individually.
+///
+/// A slice is placed in the first range long enough for it, and the range is returned and merged with
+/// the adjacent vacant ranges when the slice is freed. A slice longer than the block size gets a block of its own.
+/// The extents of the slices are kept in a `Pool`, so a `SlicePtr` to a freed slice is detected by the generation.
+pub struct SlicePool<T> {
+    blocks: Vec<Box<[MaybeUninit<T>]>>,
+    /// The vacant ranges of each block as `(start, len)`, sorted by `start` and not adjacent to each other.
+    vacant: Vec<Vec<(usize, usize)>>,
+    extents: Pool<Extent>,
+    block_size: usize,
+}
+
+impl<T> SlicePool<T> {
+    /// The number of elements in a block of a pool created by `SlicePool::new`.
+    pub const DEFAULT_BLOCK_SIZE: usize = 4096;
+
+    pub fn new() -> Self {
+        Self::with_block_size(Self::DEFAULT_BLOCK_SIZE)
+    }
+
+    /// # Panics
+    /// Panics if `block_size` is zero.
+    pub fn with_block_size(block_size: usize) -> Self {
+        assert!(block_size > 0, "block size must be positive");
+        Self {
+            blocks: Vec::new(),
+            vacant: Vec::new(),
+            extents: Pool::new(),
+            block_size,
+        }
+    }
+
+    /// Number of slices.
+    pub fn len(&self) -> usize {
+        self.extents.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.extents.is_empty()
+    }
+
+    /// Number of elements of all blocks, including the vacant ones.
+    pub fn capacity(&self) -> usize {
+        self.blocks.iter().map(|block| block.len()).sum()
+    }
+
+    /// Finds a vacant range of `len` elements, adding a block if none is long enough.
+    fn reserve(&mut self, len: usize) -> Extent {
+        if len == 0 {
+            return Extent {
+                block: 0,
+                start: 0,
+                len,
+            };
+        }
+        let found = self.vacant.iter().enumerate().find_map(|(block, ranges)| {
+            let i = ranges.iter().position(|&(_, n)| n >= len)?;
+            Some((block, i))
+        });
+        let (block, i) = found.unwrap_or_else(|| {
+            let size = len.max(self.block_size);
+            self.blocks
+                .push((0..size).map(|_| MaybeUninit::uninit()).collect());
+            self.vacant.push(alloc::vec![(0, size)]);
+            (self.blocks.len() - 1, 0)
+        });
+        let ranges = &mut self.vacant[block];
+        let (start, n) = ranges[i];
+        if n == len {
+            ranges.remove(i);
+        } else {
+            ranges[i] = (start + len, n - len);
+        }
+        Extent { block, start, len }
+    }
+
+    /// Returns the range of `extent` to the vacant ranges of its block, merging it with the adjacent ones.
+    fn release(&mut self, extent: &Extent) {
+        if extent.len == 0 {
+            return;
+        }
+        let ranges = &mut self.vacant[extent.block];
+        let i = ranges.partition_point(|&(start, _)| start < extent.start);
+        let (mut start, mut len) = (extent.start, extent.len);
+        if i < ranges.len() && start + len == ranges[i].0 {
+            len += ranges.remove(i).1;
+        }
+        if i > 0 && ranges[i - 1].0 + ranges[i - 1].1 == start {
+            start = ranges[i - 1].0;
+            len += ranges[i - 1].1;
+            ranges[i - 1] = (start, len);
+        } else {
+            ranges.insert(i, (start, len));
+        }
+    }
+
+    fn slice(&self, extent: &Extent) -> &[T] {
+        if extent.len == 0 {
+            return &[];
+        }
+        let slots = &self.blocks[extent.block][extent.start..extent.start + extent.len];
+        unsafe { &*(slots as *const [MaybeUninit<T>] as *const [T]) }
+    }
+
+    fn slice_mut<'a>(blocks: &'a mut [Box<[MaybeUninit<T>]>], extent: &Extent) -> &'a mut [T] {
+        if extent.len == 0 {
+            return &mut [];
+        }
+        let slots = &mut blocks[extent.block][extent.start..extent.start + extent.len];
+        unsafe { &mut *(slots as *mut [MaybeUninit<T>] as *mut [T]) }
+    }
+
+    /// Allocates a slice whose elements are taken from `iter`, which must yield exactly as many elements as it reports.
+    ///
+    /// # Panics
+    /// Panics if `iter` yields fewer elements than its length.
+    pub fn alloc_from_iter<I>(&mut self, iter: I) -> SlicePtr<T>
+    where
+        I: IntoIterator,
+        I::IntoIter: ExactSizeIterator<Item = T>,
+    {
+        let mut iter = iter.into_iter();
+        let extent = self.reserve(iter.len());
+        if extent.len > 0 {
+            let slots = &mut self.blocks[extent.block][extent.start..extent.start + extent.len];
+            // the elements written so far are leaked if `iter` panics
+            for slot in slots {
+                slot.write(iter.next().expect("iterator is shorter than its length"));
+            }
+        }
+        SlicePtr {
+            ptr: self.extents.alloc(extent),
+            _elem: PhantomData,
+        }
+    }
+
+    pub fn alloc_slice(&mut self, values: &[T]) -> SlicePtr<T>
+    where
+        T: Clone,
+    {
+        self.alloc_from_iter(values.iter().cloned())
+    }
+
+    /// Returns `None` if `p` is already freed.
+    ///
+    /// # Panics
+    /// Panics if `p` belongs to another pool.
+    pub fn get(&self, p: SlicePtr<T>) -> Option<&[T]> {
+        let extent = self.extents.get(p.ptr)?;
+        Some(self.slice(&extent))
+    }
+
+    pub fn get_mut(&mut self, p: SlicePtr<T>) -> Option<&mut [T]> {
+        let extent = self.extents.get(p.ptr)?;
+        Some(Self::slice_mut(&mut self.blocks, &extent))
+    }
+
+    /// Moves the elements of the slice of `p` out, or returns `None` if it is already freed.
+    pub fn take(&mut self, p: SlicePtr<T>) -> Option<Vec<T>> {
+        let extent = self.extents.take(p.ptr)?;
+        let values = self
+            .slice(&extent)
+            .iter()
+            .map(|value| unsafe { core::ptr::read(value) })
+            .collect();
+        self.release(&extent);
+        Some(values)
+    }
+
+    pub fn free(&mut self, p: SlicePtr<T>) -> bool {
+        match self.extents.take(p.ptr) {
+            Some(extent) => {
+                // the range is released even if dropping an element panics
+                let values = Self::slice_mut(&mut self.blocks, &extent) as *mut [T];
+                self.release(&extent);
+                unsafe { core::ptr::drop_in_place(values) };
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Drops all slices, keeping the blocks to be reused.
+    pub fn clear(&mut self) {
+        let ptrs = self.extents.iter().map(|(p, _)| p).collect::<Vec<_>>();
+        for ptr in ptrs {
+            self.free(SlicePtr {
+                ptr,
+                _elem: PhantomData,
+            });
+        }
+    }
+}
+
+impl<T> Drop for SlicePool<T> {
+    fn drop(&mut self) {
+        self.clear();
+    }
+}
+
+impl<T> Default for SlicePool<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: core::fmt::Debug> core::fmt::Debug for SlicePool<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_list()
+            .entries(self.extents.iter().map(|(_, extent)| self.slice(extent)))
+            .finish()
+    }
+}
+
+/// A pointer to a string in `StrPool`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct StrPtr(SlicePtr<u8>);
+
+/// A pool of strings, which are placed in shared blocks by `SlicePool`.
+#[derive(Debug, Default)]
+pub struct StrPool {
+    pool: SlicePool<u8>,
+}
+
+impl StrPool {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a pool whose blocks are of `block_size` bytes.
+    pub fn with_block_size(block_size: usize) -> Self {
+        Self {
+            pool: SlicePool::with_block_size(block_size),
+        }
+    }
+
+    /// Number of strings.
+    pub fn len(&self) -> usize {
+        self.pool.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pool.is_empty()
+    }
+
+    pub fn alloc_str(&mut self, s: &str) -> StrPtr {
+        StrPtr(self.pool.alloc_slice(s.as_bytes()))
+    }
+
+    pub fn get(&self, p: StrPtr) -> Option<&str> {
+        let bytes = self.pool.get(p.0)?;
+        Some(unsafe { core::str::from_utf8_unchecked(bytes) })
+    }
+
+    pub fn get_mut(&mut self, p: StrPtr) -> Option<&mut str> {
+        let bytes = self.pool.get_mut(p.0)?;
+        Some(unsafe { core::str::from_utf8_unchecked_mut(bytes) })
+    }
+
+    pub fn free(&mut self, p: StrPtr) -> bool {
+        self.pool.free(p.0)
+    }
+}
+
+impl<T> core::fmt::Debug for SlicePtr<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(f, "SlicePtr({:?})", self.ptr)
+    }
+}
+impl<T> Clone for SlicePtr<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+impl<T> Copy for SlicePtr<T> {}
+impl<T> PartialEq for SlicePtr<T> {
+    fn eq(&self, rhs: &Self) -> bool {
+        self.ptr == rhs.ptr
+    }
+}
+impl<T> Eq for SlicePtr<T> {}
+impl<T> core::hash::Hash for SlicePtr<T> {
+    fn hash<H: core::hash::Hasher>(&self, state: &mut H) {
+        self.ptr.hash(state)
+    }
+}
+
+#[test]
+fn test() {
+    use alloc::rc::Rc;
+
+    let mut pool = SlicePool::with_block_size(10);
+    let a = pool.alloc_slice(&[1, 2, 3]);
+    let b = pool.alloc_slice(&[4, 5, 6, 7]);
+    let c = pool.alloc_slice(&[8, 9, 10]);
+    let empty = pool.alloc_slice(&[]);
+    assert_eq!((pool.len(), pool.capacity()), (4, 10));
+    assert_eq!(pool.get(b), Some(&[4, 5, 6, 7][..]));
+    assert_eq!(pool.get(empty), Some(&[][..]));
+    pool.get_mut(a).unwrap()[0] = 0;
+    assert_eq!(pool.take(a), Some(alloc::vec![0, 2, 3]));
+    assert!(pool.free(b) && !pool.free(b));
+    assert!(pool.get(a).is_none() && pool.get(b).is_none());
+    // the vacant ranges of `a` and `b` are merged into one
+    assert_eq!(pool.vacant[0], [(0, 7)]);
+    let d = pool.alloc_slice(&[11; 7]);
+    assert_eq!(pool.capacity(), 10);
+    let long = pool.alloc_from_iter(0..25);
+    assert_eq!(pool.capacity(), 35);
+    assert_eq!(pool.get(long).unwrap().iter().sum::<i32>(), 300);
+    assert_eq!(pool.get(d).unwrap(), [11; 7]);
+    assert_eq!(pool.get(c).unwrap(), [8, 9, 10]);
+    pool.clear();
+    assert!(pool.is_empty());
+    assert_eq!(pool.vacant, [[(0, 10)], [(0, 25)]]);
+
+    let rc = Rc::new(());
+    let mut pool = SlicePool::with_block_size(4);
+    let ptrs = (0..5)
+        .map(|n| pool.alloc_slice(&alloc::vec![rc.clone(); n]))
+        .collect::<Vec<_>>();
+    assert_eq!(Rc::strong_count(&rc), 11);
+    pool.free(ptrs[4]);
+    assert_eq!(pool.take(ptrs[3]).map(|v| v.len()), Some(3));
+    assert_eq!(Rc::strong_count(&rc), 4);
+    drop(pool);
+    assert_eq!(Rc::strong_count(&rc), 1);
+
+    let mut pool = StrPool::with_block_size(16);
+    let hello = pool.alloc_str("hello");
+    let world = pool.alloc_str("world");
+    pool.get_mut(hello).unwrap().make_ascii_uppercase();
+    assert_eq!(pool.get(hello), Some("HELLO"));
+    assert!(pool.free(world));
+    assert_eq!((pool.get(world), pool.len()), (None, 1));
+}
@@ -0,0 +1,278 @@
+use crate::Ptr32;
+use alloc::vec::Vec;
+use core::convert::TryFrom;
+
+/// A struct whose values are stored field by field in `SoAPool`, which is implemented by `#[derive(PooledSoA)]`
+/// with the `derive` feature.
+///
+/// # Safety
+/// `Columns` must hold a column of slots for every field, in which `push_slot` appends an uninitialized slot,
+/// and the other methods must access only the slots at `index` of the columns.
+pub unsafe trait PooledSoA: Sized {
+    /// The columns of the slots of the fields, which are kept possibly uninitialized.
+    type Columns: Default;
+    /// The references to the fields of a value.
+    type Ref<'a>
+    where
+        Self: 'a;
+    /// The mutable references to the fields of a value.
+    type Mut<'a>
+    where
+        Self: 'a;
+
+    /// Appends an uninitialized slot to every column.
+    fn push_slot(columns: &mut Self::Columns);
+
+    /// Writes the fields of `value` to the slots at `index`, without dropping the old ones.
+    ///
+    /// # Safety
+    /// The slots at `index` must exist.
+    unsafe fn write(columns: &mut Self::Columns, index: usize, value: Self);
+
+    /// Moves the value out of the slots at `index`, which are left to be uninitialized.
+    ///
+    /// # Safety
+    /// The slots at `index` must be initialized.
+    unsafe fn read(columns: &Self::Columns, index: usize) -> Self;
+
+    /// # Safety
+    /// The slots at `index` must be initialized.
+    unsafe fn get(columns: &Self::Columns, index: usize) -> Self::Ref<'_>;
+
+    /// # Safety
+    /// The slots at `index` must be initialized, and not borrowed by others for `'a`.
+    /// The slots at other indices may be borrowed meanwhile.
+    unsafe fn get_mut<'a>(columns: *mut Self::Columns, index: usize) -> Self::Mut<'a>
+    where
+        Self: 'a;
+}
+
+/// A pool which stores the fields of the values in their own columns, while a `Ptr32` refers to a whole value.
+/// Reading a field across the values touches only the column of the field,
+/// unlike `Pool` where the values are laid out one after another.
+/// The occupancy is kept in a bitmap and the generations in a vector beside the columns.
+pub struct SoAPool<T: PooledSoA> {
+    columns: T::Columns,
+    occupied: Vec<u64>,
+    generations: Vec<u32>,
+    vacant: Vec<u32>,
+    len: usize,
+}
+
+impl<T: PooledSoA> SoAPool<T> {
+    pub fn new() -> Self {
+        Self {
+            columns: T::Columns::default(),
+            occupied: Vec::new(),
+            generations: Vec::new(),
+            vacant: Vec::new(),
+            len: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.generations.len()
+    }
+
+    fn is_occupied(&self, index: usize) -> bool {
+        self.occupied[index / 64] & (1 << (index % 64)) != 0
+    }
+
+    /// Returns the index of the slots of `p` if they are occupied by the generation of `p`.
+    fn index(&self, p: Ptr32<T>) -> Option<usize> {
+        let index = p.index() as usize;
+        (index < self.capacity()
+            && self.is_occupied(index)
+            && self.generations[index] == p.generation())
+        .then_some(index)
+    }
+
+    /// # Panics
+    /// Panics if the number of slots exceeds `u32::MAX`.
+    pub fn alloc(&mut self, value: T) -> Ptr32<T> {
+        let index = match self.vacant.pop() {
+            Some(index) => index as usize,
+            None => {
+                let index = self.capacity();
+                u32::try_from(index).expect("pool is too large for Ptr32");
+                T::push_slot(&mut self.columns);
+                self.generations.push(0);
+                self.occupied.resize((index + 1).div_ceil(64), 0);
+                index
+            }
+        };
+        unsafe { T::write(&mut self.columns, index, value) };
+        self.occupied[index / 64] |= 1 << (index % 64);
+        self.len += 1;
+        Ptr32::new(index as u32, self.generations[index])
+    }
+
+    pub fn get(&self, p: Ptr32<T>) -> Option<T::Ref<'_>> {
+        let index = self.index(p)?;
+        Some(unsafe { T::get(&self.columns, index) })
+    }
+
+    pub fn get_mut(&mut self, p: Ptr32<T>) -> Option<T::Mut<'_>> {
+        let index = self.index(p)?;
+        Some(unsafe { T::get_mut(&mut self.columns, index) })
+    }
+
+    /// Moves the value of `p` out, or returns `None` if it is already freed.
+    pub fn take(&mut self, p: Ptr32<T>) -> Option<T> {
+        let index = self.index(p)?;
+        self.vacate(index);
+        Some(unsafe { T::read(&self.columns, index) })
+    }
+
+    pub fn free(&mut self, p: Ptr32<T>) -> bool {
+        self.take(p).is_some()
+    }
+
+    /// Marks the slots vacant of the next generation, before the value is moved out.
+    fn vacate(&mut self, index: usize) {
+        self.occupied[index / 64] &= !(1 << (index % 64));
+        self.generations[index] = self.generations[index].wrapping_add(1);
+        self.vacant.push(index as u32);
+        self.len -= 1;
+    }
+
+    /// Iterates over the values in the order of the slots.
+    /// The references are to the columns, so reading only some fields of them does not load the other columns.
+    pub fn iter(&self) -> impl Iterator<Item = (Ptr32<T>, T::Ref<'_>)> {
+        set_bits(&self.occupied).map(move |index| {
+            let p = Ptr32::new(index as u32, self.generations[index]);
+            (p, unsafe { T::get(&self.columns, index) })
+        })
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (Ptr32<T>, T::Mut<'_>)> {
+        let columns = &mut self.columns as *mut T::Columns;
+        let generations = &self.generations;
+        // each index is visited once, so the slots borrowed do not overlap
+        set_bits(&self.occupied).map(move |index| {
+            let p = Ptr32::new(index as u32, generations[index]);
+            (p, unsafe { T::get_mut(columns, index) })
+        })
+    }
+
+    /// Drops all values, keeping the columns to be reused.
+    pub fn clear(&mut self) {
+        let indices = set_bits(&self.occupied).collect::<Vec<_>>();
+        for index in indices {
+            let p = Ptr32::new(index as u32, self.generations[index]);
+            self.free(p);
+        }
+    }
+}
+
+/// Iterates over the indices of the set bits of a bitmap in order, skipping 64 bits at a time if they are clear.
+fn set_bits(words: &[u64]) -> impl Iterator<Item = usize> + '_ {
+    words.iter().enumerate().flat_map(|(i, &word)| {
+        let mut word = word;
+        core::iter::from_fn(move || {
+            if word == 0 {
+                return None;
+            }
+            let bit = word.trailing_zeros() as usize;
+            word &= word - 1;
+            Some(64 * i + bit)
+        })
+    })
+}
+
+impl<T: PooledSoA> Drop for SoAPool<T> {
+    fn drop(&mut self) {
+        self.clear();
+    }
+}
+
+impl<T: PooledSoA> Default for SoAPool<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: PooledSoA> core::fmt::Debug for SoAPool<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(f, "SoAPool {{ len: {} }}", self.len)
+    }
+}
+
+#[cfg(feature = "derive")]
+#[test]
+fn test() {
+    use crate::PooledSoA;
+    use alloc::rc::Rc;
+    use alloc::string::String;
+
+    #[derive(PooledSoA)]
+    struct Particle {
+        pos: [f32; 3],
+        mass: f32,
+        name: String,
+    }
+    let mut pool = SoAPool::new();
+    let ptrs = (0..100)
+        .map(|i| {
+            pool.alloc(Particle {
+                pos: [i as f32, 0.0, 0.0],
+                mass: 1.0,
+                name: i.to_string(),
+            })
+        })
+        .collect::<Vec<_>>();
+    for &p in ptrs.iter().step_by(2) {
+        assert!(pool.free(p));
+    }
+    assert!(!pool.free(ptrs[0]) && pool.get(ptrs[0]).is_none());
+    assert_eq!((pool.len(), pool.capacity()), (50, 100));
+    assert_eq!(pool.iter().map(|(_, r)| *r.mass).sum::<f32>(), 50.0);
+    for (_, r) in pool.iter_mut() {
+        r.pos[1] = r.pos[0] * 2.0;
+        *r.mass += 1.0;
+    }
+    let r = pool.get(ptrs[3]).unwrap();
+    assert_eq!(
+        (*r.pos, *r.mass, r.name.as_str()),
+        ([3.0, 6.0, 0.0], 2.0, "3")
+    );
+    pool.get_mut(ptrs[3]).unwrap().name.push('!');
+    let taken = pool.take(ptrs[3]).unwrap();
+    assert_eq!(taken.name, "3!");
+    let p = pool.alloc(taken);
+    assert_eq!((p.index(), p.generation()), (3, 1));
+    assert!(pool
+        .iter()
+        .all(|(p, r)| ptrs[p.index() as usize] == p || r.name == "3!"));
+
+    #[derive(PooledSoA)]
+    pub struct Pair<A, B: Clone> {
+        pub a: A,
+        b: B,
+    }
+    let rc = Rc::new(());
+    let mut pool = SoAPool::new();
+    let ptrs = (0..10)
+        .map(|i| {
+            pool.alloc(Pair {
+                a: i,
+                b: rc.clone(),
+            })
+        })
+        .collect::<Vec<_>>();
+    pool.free(ptrs[0]);
+    let pair = pool.take(ptrs[1]).unwrap();
+    assert_eq!((pair.a, Rc::strong_count(&pair.b)), (1, 10));
+    drop(pair);
+    assert_eq!(*pool.get(ptrs[9]).unwrap().a, 9);
+    drop(pool);
+    assert_eq!(Rc::strong_count(&rc), 1);
+}
@@ -0,0 +1,223 @@
+use crate::Entry;
+use alloc::boxed::Box;
+use core::alloc::Layout;
+use core::marker::PhantomData;
+use core::ops::{Deref, DerefMut};
+use core::ptr::NonNull;
+
+/// An allocator of the blocks of `Pool`, e.g. a bump arena or a static buffer instead of the global heap.
+///
+/// # Safety
+/// The entries of a block must stay at the same addresses until the block is dropped,
+/// even if the block itself is moved. The block must drop its entries when it is dropped,
+/// and must not otherwise use the values then, since they may borrow data dropped before, as `Box` allows.
+pub unsafe trait BlockStorage {
+    type Block<T>: DerefMut<Target = [Entry<T>]>;
+
+    /// Whether `Pool` may reorder or release the blocks, e.g. by `swap_blocks` or `shrink_to_fit`.
+    /// A storage which identifies the blocks by the order of their allocation, like `Mapped`, disables it.
+    const REORDERABLE: bool = true;
+
+    /// Allocates a block of `len` entries, each of which is made by `f`.
+    fn alloc_block<T>(&self, len: usize, f: impl FnMut() -> Entry<T>) -> Self::Block<T>;
+}
+
+/// A block of `Pool`, which owns a block of its storage without naming `S::Block<T>`,
+/// so that `Pool<T>` stays covariant in `T` and its values may borrow one another like in `Box<[Entry<T>]>`.
+pub(crate) struct Block<T> {
+    entries: NonNull<[Entry<T>]>,
+    _owner: Owner,
+    _marker: PhantomData<Entry<T>>,
+}
+
+/// The type-erased block of a storage, dropped by `drop`.
+struct Owner {
+    block: NonNull<()>,
+    drop: unsafe fn(NonNull<()>),
+}
+
+impl Drop for Owner {
+    fn drop(&mut self) {
+        unsafe { (self.drop)(self.block) }
+    }
+}
+
+unsafe impl<T: Sync> Sync for Block<T> {}
+
+impl<T> Block<T> {
+    pub(crate) fn new<B: DerefMut<Target = [Entry<T>]>>(block: B) -> Self {
+        unsafe fn drop_block<B>(block: NonNull<()>) {
+            drop(Box::from_raw(block.cast::<B>().as_ptr()));
+        }
+        let block = Box::leak(Box::new(block));
+        Self {
+            entries: NonNull::from(&mut **block),
+            _owner: Owner {
+                block: NonNull::from(block).cast(),
+                drop: drop_block::<B>,
+            },
+            _marker: PhantomData,
+        }
+    }
+
+    /// Borrows the entry at `slot` through the raw pointer, without borrowing the other entries.
+    pub(crate) fn entry_mut(&mut self, slot: usize) -> &mut Entry<T> {
+        assert!(slot < self.entries.len());
+        unsafe { &mut *self.entries.cast::<Entry<T>>().as_ptr().add(slot) }
+    }
+}
+
+impl<T> Deref for Block<T> {
+    type Target = [Entry<T>];
+    fn deref(&self) -> &[Entry<T>] {
+        unsafe { self.entries.as_ref() }
+    }
+}
+
+impl<T> DerefMut for Block<T> {
+    fn deref_mut(&mut self) -> &mut [Entry<T>] {
+        unsafe { self.entries.as_mut() }
+    }
+}
+
+/// The global heap, from which `Pool` allocates its blocks by default.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Heap;
+
+unsafe impl BlockStorage for Heap {
+    type Block<T> = Box<[Entry<T>]>;
+
+    fn alloc_block<T>(&self, len: usize, mut f: impl FnMut() -> Entry<T>) -> Self::Block<T> {
+        (0..len).map(|_| f()).collect()
+    }
+}
+
+/// A storage which aligns the start of every block to `align` bytes, e.g. to a cache line or a huge page.
+/// To align every value rather than the blocks, give `T` the alignment by `#[repr(align(N))]`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Aligned {
+    align: usize,
+}
+
+impl Aligned {
+    /// # Panics
+    /// Panics if `align` is not a power of two.
+    pub fn new(align: usize) -> Self {
+        assert!(align.is_power_of_two(), "alignment must be a power of two");
+        Self { align }
+    }
+
+    pub fn align(&self) -> usize {
+        self.align
+    }
+}
+
+/// A block allocated by `Aligned`.
+pub struct AlignedBlock<T> {
+    ptr: NonNull<Entry<T>>,
+    len: usize,
+    layout: Layout,
+    _marker: PhantomData<Entry<T>>,
+}
+
+unsafe impl<T: Send> Send for AlignedBlock<T> {}
+unsafe impl<T: Sync> Sync for AlignedBlock<T> {}
+
+impl<T> Deref for AlignedBlock<T> {
+    type Target = [Entry<T>];
+    fn deref(&self) -> &[Entry<T>] {
+        unsafe { core::slice::from_raw_parts(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl<T> DerefMut for AlignedBlock<T> {
+    fn deref_mut(&mut self) -> &mut [Entry<T>] {
+        unsafe { core::slice::from_raw_parts_mut(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl<T> Drop for AlignedBlock<T> {
+    fn drop(&mut self) {
+        unsafe {
+            core::ptr::drop_in_place(&mut **self as *mut [Entry<T>]);
+            if self.layout.size() > 0 {
+                alloc::alloc::dealloc(self.ptr.as_ptr().cast(), self.layout);
+            }
+        }
+    }
+}
+
+unsafe impl BlockStorage for Aligned {
+    type Block<T> = AlignedBlock<T>;
+
+    fn alloc_block<T>(&self, len: usize, mut f: impl FnMut() -> Entry<T>) -> AlignedBlock<T> {
+        let layout = Layout::array::<Entry<T>>(len)
+            .and_then(|layout| layout.align_to(self.align))
+            .expect("block is too large");
+        let ptr = if layout.size() == 0 {
+            NonNull::new(layout.align() as *mut Entry<T>).unwrap()
+        } else {
+            let ptr = unsafe { alloc::alloc::alloc(layout) };
+            match NonNull::new(ptr.cast()) {
+                Some(ptr) => ptr,
+                None => alloc::alloc::handle_alloc_error(layout),
+            }
+        };
+        // the entries made so far are dropped by the block even if `f` panics
+        let mut block = AlignedBlock {
+            ptr,
+            len: 0,
+            layout,
+            _marker: PhantomData,
+        };
+        while block.len < len {
+            unsafe { block.ptr.as_ptr().add(block.len).write(f()) };
+            block.len += 1;
+        }
+        block
+    }
+}
+
+#[test]
+fn test() {
+    use crate::Pool;
+    use alloc::vec::Vec;
+    use core::cell::Cell;
+
+    #[derive(Default)]
+    struct Counting(Cell<usize>);
+    unsafe impl BlockStorage for Counting {
+        type Block<T> = Vec<Entry<T>>;
+        fn alloc_block<T>(&self, len: usize, f: impl FnMut() -> Entry<T>) -> Vec<Entry<T>> {
+            self.0.set(self.0.get() + len);
+            core::iter::repeat_with(f).take(len).collect()
+        }
+    }
+
+    let mut pool = Pool::with_block_size_in(4, Counting::default());
+    let ptrs = (0..10).map(|i| pool.alloc(i)).collect::<Vec<_>>();
+    assert_eq!(pool.storage().0.get(), 12);
+    pool.free(ptrs[3]);
+    assert_eq!(pool.iter().map(|(_, &i)| i).sum::<i32>(), 42);
+    pool.assert_consistent();
+    assert_eq!(pool.into_sorted_vec(|&i| -i)[0], 9);
+}
+
+#[test]
+fn aligned() {
+    use crate::Pool;
+    use alloc::rc::Rc;
+
+    let rc = Rc::new(());
+    let mut pool = Pool::with_block_size_in(3, Aligned::new(4096));
+    let ptrs = (0..10).map(|_| pool.alloc(rc.clone())).collect::<Vec<_>>();
+    assert_eq!(pool.storage().align(), 4096);
+    assert!(pool
+        .iter_blocks()
+        .all(|block| (block.as_ptr() as usize).is_multiple_of(4096)));
+    pool.free(ptrs[4]);
+    assert_eq!(Rc::strong_count(&rc), 10);
+    pool.assert_consistent();
+    drop(pool);
+    assert_eq!(Rc::strong_count(&rc), 1);
+}